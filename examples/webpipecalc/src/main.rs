@@ -259,116 +259,122 @@ fn render_table(model: &Model) -> (String, String) {
     csv.push_str("\n");
 
     let temp = pipecalc::Temperament::new_freq_equal(model.standard_pitch);
+    // Same spelling as earlier versions of the table: sharps, but Bb instead of A#
+    let sharp_format = pipecalc::PitchFormat::new();
+    let mut flat_format = pipecalc::PitchFormat::new();
+    flat_format.set_flats(true);
+    let mut scale = pipecalc::Scale::new_normal();
+    scale.set_offset(model.tnm_distance);
+
+    let compass = pipecalc::PitchRange::new(
+        pipecalc::Pitch::from(0, model.first_octave as i32),
+        pipecalc::Pitch::from(11, model.last_octave as i32),
+    );
 
-    for octave in model.first_octave..(model.last_octave + 1) {
-        for note in 0..12 {
-            let pitch = pipecalc::Pitch::from(note, octave as i32);
-            let tnm_pitch = pitch.add(-(model.tnm_distance * 100.0));
-            let steps = pitch.difference_to(&temp.get_base_note());
-            let frequency = temp.get_frequency(steps as f64);
-            let radius = tnm_pitch.get_tnm_radius();
-
-            let mut pipe = pipecalc::Pipe::new();
-            pipe.set_frequency(frequency)
-                .set_stopped(model.stopped)
-                .set_intonation_number(model.intonation_number)
-                .set_mouth_ratio(model.mouth_ratio)
-                .set_cutup_ratio(model.cutup_ratio)
-                .set_radius(radius)
-                .set_air_temperature(model.temperature)
-                .set_air_density(model.air_density)
-                .set_wind_pressure(model.air_pressure);
-
-            let dimensions = pipe.get_dimensions();
-
-            let note_name = match note {
-                0 => "C",
-                1 => "C#",
-                2 => "D",
-                3 => "D#",
-                4 => "E",
-                5 => "F",
-                6 => "F#",
-                7 => "G",
-                8 => "G#",
-                9 => "A",
-                10 => "Bb",
-                11 => "B",
-                _ => panic!("Invalid note number"),
-            };
-
-            let mut formatted_note_name = String::from(r#"$\text{"#);
-            formatted_note_name.push_str(&format!("{}", note_name));
-            formatted_note_name.push_str("}_{");
-            formatted_note_name.push_str(&format!("{}", octave));
-            formatted_note_name.push_str("}$");
-
-            html.push_str("<tr>");
-            html.push_str(&format!("<td>{}</td>", formatted_note_name));
-            csv.push_str(&format!("{}{},", note_name, octave));
-            add_entry(&format!("{:.2}", frequency), &mut html, &mut csv, false);
-            add_double_entry(
-                &format!("{:.4}", dimensions.theoretical_resonator_length * 1000.0),
-                &format!("{:.4}", dimensions.resonator_length * 1000.0),
-                &mut html,
-                &mut csv,
-            );
-            add_entry(
-                &format!("{:.4}", radius * 2.0 * 1000.0),
-                &mut html,
-                &mut csv,
-                false,
-            );
-            add_entry(
-                &format!("{:.4}", dimensions.cross_section * 1000.0 * 1000.0),
-                &mut html,
-                &mut csv,
-                false,
-            );
-            add_entry(
-                &format!("{:.4}", dimensions.mouth_height * 1000.0),
-                &mut html,
-                &mut csv,
-                false,
-            );
-            add_entry(
-                &format!("{:.4}", dimensions.mouth_width * 1000.0),
-                &mut html,
-                &mut csv,
-                false,
-            );
-            add_entry(
-                &format!("{:.4}", dimensions.pipe_depth * 1000.0),
-                &mut html,
-                &mut csv,
-                false,
-            );
-            add_entry(
-                &format!("{:.4}", dimensions.jet_thickness * 1000.0),
-                &mut html,
-                &mut csv,
-                false,
-            );
-            add_entry(
-                &format!("{:.4}", dimensions.air_hole_diameter * 1000.0),
-                &mut html,
-                &mut csv,
-                false,
-            );
-            add_entry(
-                &format!("{:.8}", dimensions.air_consumption_rate),
-                &mut html,
-                &mut csv,
-                false,
-            );
-            add_entry(
-                &format!("{:.8}\n", dimensions.sound_power),
-                &mut html,
-                &mut csv,
-                true,
-            );
-            html.push_str("</tr>");
-        }
+    for pitch in compass {
+        let steps = pitch.difference_to(&temp.get_base_note());
+        let frequency = temp.get_frequency(steps as f64);
+        let radius = scale.get_radius(&pitch);
+
+        let mut pipe = pipecalc::Pipe::new();
+        pipe.set_frequency(frequency)
+            .set_stopped(model.stopped)
+            .set_intonation_number(model.intonation_number)
+            .set_mouth_ratio(model.mouth_ratio)
+            .set_cutup_ratio(model.cutup_ratio)
+            .set_radius(radius)
+            .set_air_temperature(model.temperature)
+            .set_air_density(model.air_density)
+            .set_wind_pressure(model.air_pressure);
+
+        let note_format = if pitch.get_note() == 10 {
+            &flat_format
+        } else {
+            &sharp_format
+        };
+        let note_name = note_format.format_name(&pitch);
+
+        let mut formatted_note_name = String::from(r#"$\text{"#);
+        formatted_note_name.push_str(&note_name);
+        formatted_note_name.push_str("}_{");
+        formatted_note_name.push_str(&format!("{}", pitch.get_octave()));
+        formatted_note_name.push_str("}$");
+
+        html.push_str("<tr>");
+        html.push_str(&format!("<td>{}</td>", formatted_note_name));
+        csv.push_str(&format!("{},", note_format.format(&pitch)));
+
+        let dimensions = match pipe.try_get_dimensions() {
+            Ok(dimensions) => dimensions,
+            Err(error) => {
+                html.push_str(&format!("<td colspan=\"11\">{}</td></tr>", error));
+                csv.push_str(&format!("\"{}\"\n", error));
+                continue;
+            }
+        };
+
+        add_entry(&format!("{:.2}", frequency), &mut html, &mut csv, false);
+        add_double_entry(
+            &format!("{:.4}", dimensions.theoretical_resonator_length * 1000.0),
+            &format!("{:.4}", dimensions.resonator_length * 1000.0),
+            &mut html,
+            &mut csv,
+        );
+        add_entry(
+            &format!("{:.4}", radius * 2.0 * 1000.0),
+            &mut html,
+            &mut csv,
+            false,
+        );
+        add_entry(
+            &format!("{:.4}", dimensions.cross_section * 1000.0 * 1000.0),
+            &mut html,
+            &mut csv,
+            false,
+        );
+        add_entry(
+            &format!("{:.4}", dimensions.mouth_height * 1000.0),
+            &mut html,
+            &mut csv,
+            false,
+        );
+        add_entry(
+            &format!("{:.4}", dimensions.mouth_width * 1000.0),
+            &mut html,
+            &mut csv,
+            false,
+        );
+        add_entry(
+            &format!("{:.4}", dimensions.pipe_depth * 1000.0),
+            &mut html,
+            &mut csv,
+            false,
+        );
+        add_entry(
+            &format!("{:.4}", dimensions.jet_thickness * 1000.0),
+            &mut html,
+            &mut csv,
+            false,
+        );
+        add_entry(
+            &format!("{:.4}", dimensions.air_hole_diameter * 1000.0),
+            &mut html,
+            &mut csv,
+            false,
+        );
+        add_entry(
+            &format!("{:.8}", dimensions.air_consumption_rate),
+            &mut html,
+            &mut csv,
+            false,
+        );
+        add_entry(
+            &format!("{:.8}\n", dimensions.sound_power),
+            &mut html,
+            &mut csv,
+            true,
+        );
+        html.push_str("</tr>");
     }
 
     html.push_str("</tbody></table>");
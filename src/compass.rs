@@ -0,0 +1,314 @@
+// This file is part of pipecalc.
+//
+// pipecalc is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// pipecalc is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with pipecalc.  If not, see <http://www.gnu.org/licenses/>.
+
+use notation::PitchFormat;
+use temperament::Pitch;
+
+// Inclusive range of pitches, descending for negative steps
+#[derive(Clone, PartialEq, Debug)]
+pub struct PitchRange {
+    next: Pitch,
+    end: Pitch,
+
+    // in semitones
+    step: i32,
+}
+
+impl PitchRange {
+    pub fn new(start: Pitch, end: Pitch) -> Self {
+        PitchRange::with_step(start, end, 1)
+    }
+
+    pub fn with_step(start: Pitch, end: Pitch, step: i32) -> Self {
+        assert!(step != 0, "PitchRange step must not be 0");
+        PitchRange {
+            next: start,
+            end,
+            step,
+        }
+    }
+
+    pub fn get_start(&self) -> &Pitch {
+        &self.next
+    }
+
+    pub fn get_end(&self) -> &Pitch {
+        &self.end
+    }
+
+    pub fn get_step(&self) -> i32 {
+        self.step
+    }
+
+    // True if pitch lies between the remaining start and the end, regardless of the step
+    pub fn contains(&self, pitch: &Pitch) -> bool {
+        let from_start = pitch.difference_to(&self.next) * f64::from(self.step.signum());
+        let to_end = self.end.difference_to(pitch) * f64::from(self.step.signum());
+        from_start > -1e-9 && to_end > -1e-9
+    }
+}
+
+impl Iterator for PitchRange {
+    type Item = Pitch;
+
+    fn next(&mut self) -> Option<Pitch> {
+        let remaining = self.end.difference_to(&self.next) * f64::from(self.step.signum());
+        if remaining < -1e-9 {
+            return None;
+        }
+
+        let pitch = self.next.clone();
+        self.next = pitch.add(f64::from(self.step) * 100.0);
+        Some(pitch)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.end.difference_to(&self.next) / f64::from(self.step);
+        let count = if remaining < -1e-9 {
+            0
+        } else {
+            (remaining + 1e-9).floor() as usize + 1
+        };
+        (count, Some(count))
+    }
+}
+
+impl ExactSizeIterator for PitchRange {}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum KeyHalf {
+    Whole,
+
+    // Split keys of broken octaves
+    Front,
+    Back,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Key {
+    // Pitch of the key on a chromatic keyboard
+    pub position: Pitch,
+    pub half: KeyHalf,
+    pub sounding: Pitch,
+}
+
+// Keys of a manual or pedal in playing order, each mapped to the pitch it sounds
+#[derive(Clone, PartialEq, Debug)]
+pub struct Compass {
+    keys: Vec<Key>,
+}
+
+impl Compass {
+    pub fn new() -> Self {
+        Compass { keys: Vec::new() }
+    }
+
+    pub fn new_chromatic(range: PitchRange) -> Self {
+        let mut compass = Compass::new();
+        for pitch in range {
+            compass.add_key(pitch.clone(), pitch);
+        }
+        compass
+    }
+
+    // C/E short octave: the E key sounds C, F# sounds D and G# sounds E
+    pub fn new_short_octave_ce(octave: i32, end: Pitch) -> Self {
+        let mut compass = Compass::new();
+        compass
+            .add_key(Pitch::from(4, octave), Pitch::from(0, octave))
+            .add_key(Pitch::from(5, octave), Pitch::from(5, octave))
+            .add_key(Pitch::from(6, octave), Pitch::from(2, octave))
+            .add_key(Pitch::from(7, octave), Pitch::from(7, octave))
+            .add_key(Pitch::from(8, octave), Pitch::from(4, octave))
+            .extend_chromatic(Pitch::from(9, octave), end);
+        compass
+    }
+
+    // G/H short octave: the H key sounds G, C# sounds A and D# sounds H
+    pub fn new_short_octave_gh(octave: i32, end: Pitch) -> Self {
+        let mut compass = Compass::new();
+        compass
+            .add_key(Pitch::from(11, octave), Pitch::from(7, octave))
+            .add_key(Pitch::from(12, octave), Pitch::from(12, octave))
+            .add_key(Pitch::from(13, octave), Pitch::from(9, octave))
+            .add_key(Pitch::from(14, octave), Pitch::from(14, octave))
+            .add_key(Pitch::from(15, octave), Pitch::from(11, octave))
+            .extend_chromatic(Pitch::from(16, octave), end);
+        compass
+    }
+
+    // C/E broken octave: the fronts of the split F# and G# keys sound D and E,
+    // their backs F# and G#
+    pub fn new_broken_octave_ce(octave: i32, end: Pitch) -> Self {
+        let mut compass = Compass::new();
+        compass
+            .add_key(Pitch::from(4, octave), Pitch::from(0, octave))
+            .add_key(Pitch::from(5, octave), Pitch::from(5, octave))
+            .add_split_key(
+                Pitch::from(6, octave),
+                Pitch::from(2, octave),
+                Pitch::from(6, octave),
+            )
+            .add_key(Pitch::from(7, octave), Pitch::from(7, octave))
+            .add_split_key(
+                Pitch::from(8, octave),
+                Pitch::from(4, octave),
+                Pitch::from(8, octave),
+            )
+            .extend_chromatic(Pitch::from(9, octave), end);
+        compass
+    }
+
+    pub fn add_key(&mut self, position: Pitch, sounding: Pitch) -> &mut Self {
+        self.keys.push(Key {
+            position,
+            half: KeyHalf::Whole,
+            sounding,
+        });
+        self
+    }
+
+    pub fn add_split_key(&mut self, position: Pitch, front: Pitch, back: Pitch) -> &mut Self {
+        self.keys.push(Key {
+            position: position.clone(),
+            half: KeyHalf::Front,
+            sounding: front,
+        });
+        self.keys.push(Key {
+            position,
+            half: KeyHalf::Back,
+            sounding: back,
+        });
+        self
+    }
+
+    pub fn extend_chromatic(&mut self, start: Pitch, end: Pitch) -> &mut Self {
+        for pitch in PitchRange::new(start, end) {
+            self.add_key(pitch.clone(), pitch);
+        }
+        self
+    }
+
+    pub fn get_keys(&self) -> &[Key] {
+        &self.keys
+    }
+
+    // Pitches a rank needs pipes for, ascending and without duplicates
+    pub fn get_sounding_pitches(&self) -> Vec<Pitch> {
+        let mut pitches: Vec<Pitch> = self.keys.iter().map(|key| key.sounding.clone()).collect();
+        pitches.sort_by(|a, b| a.difference_to(b).partial_cmp(&0.0).unwrap());
+        pitches.dedup_by(|a, b| a.difference_to(b).abs() < 1e-9);
+        pitches
+    }
+
+    // One line per key: key, tab, sounding pitch
+    pub fn report(&self, format: &PitchFormat) -> String {
+        let mut report = String::from("Key\tSounding\n");
+        for key in &self.keys {
+            let half = match key.half {
+                KeyHalf::Whole => "",
+                KeyHalf::Front => " (front)",
+                KeyHalf::Back => " (back)",
+            };
+            report.push_str(&format!(
+                "{}{}\t{}\n",
+                format.format(&key.position),
+                half,
+                format.format(&key.sounding)
+            ));
+        }
+        report
+    }
+}
+
+impl Default for Compass {
+    fn default() -> Self {
+        Compass::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pitch_range() {
+        // C-g''' manual compass
+        let compass = PitchRange::new(Pitch::from(0, 2), Pitch::from(7, 6));
+        assert_eq!(compass.len(), 56);
+        let pitches: Vec<Pitch> = compass.collect();
+        assert_eq!(pitches.len(), 56);
+        assert_eq!(pitches[0], Pitch::from(0, 2));
+        assert_eq!(pitches[13], Pitch::from(1, 3));
+        assert_eq!(pitches[55], Pitch::from(7, 6));
+
+        let whole_tones: Vec<Pitch> =
+            PitchRange::with_step(Pitch::from(0, 2), Pitch::from(11, 2), 2).collect();
+        assert_eq!(whole_tones.len(), 6);
+        assert_eq!(whole_tones[5], Pitch::from(10, 2));
+
+        let descending: Vec<Pitch> =
+            PitchRange::with_step(Pitch::from(7, 3), Pitch::from(0, 2), -12).collect();
+        assert_eq!(descending, vec![Pitch::from(7, 3), Pitch::from(7, 2)]);
+
+        assert_eq!(
+            PitchRange::new(Pitch::from(0, 3), Pitch::from(0, 2)).count(),
+            0
+        );
+
+        let compass = PitchRange::new(Pitch::from(0, 2), Pitch::from(7, 3));
+        assert!(compass.contains(&Pitch::from(4, 3)));
+        assert!(compass.contains(&Pitch::from(7, 3)));
+        assert!(!compass.contains(&Pitch::from(8, 3)));
+        assert!(!compass.contains(&Pitch::from(11, 1)));
+    }
+
+    #[test]
+    fn test_short_octaves() {
+        // C/E to C6 with short octave
+        let compass = Compass::new_short_octave_ce(2, Pitch::from(0, 6));
+        assert_eq!(compass.get_keys().len(), 45);
+        assert_eq!(compass.get_keys()[0].position, Pitch::from(4, 2));
+        assert_eq!(compass.get_keys()[0].sounding, Pitch::from(0, 2));
+        let pitches = compass.get_sounding_pitches();
+        assert_eq!(pitches.len(), 45);
+        assert_eq!(
+            &pitches[..5],
+            &[
+                Pitch::from(0, 2),
+                Pitch::from(2, 2),
+                Pitch::from(4, 2),
+                Pitch::from(5, 2),
+                Pitch::from(7, 2),
+            ]
+        );
+        assert_eq!(pitches[44], Pitch::from(0, 6));
+
+        let compass = Compass::new_short_octave_gh(1, Pitch::from(0, 3));
+        let pitches = compass.get_sounding_pitches();
+        assert_eq!(pitches.len(), 14);
+        assert_eq!(pitches[0], Pitch::from(7, 1));
+        assert_eq!(pitches[1], Pitch::from(9, 1));
+        assert_eq!(pitches[2], Pitch::from(11, 1));
+        assert_eq!(pitches[3], Pitch::from(0, 2));
+
+        let compass = Compass::new_broken_octave_ce(2, Pitch::from(0, 3));
+        assert_eq!(compass.get_keys().len(), 11);
+        assert_eq!(compass.get_sounding_pitches().len(), 11);
+        let report = compass.report(&PitchFormat::new());
+        assert!(report.starts_with("Key\tSounding\nE2\tC2\nF2\tF2\nF#2 (front)\tD2\n"));
+        assert!(report.contains("F#2 (back)\tF#2\n"));
+    }
+}
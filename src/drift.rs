@@ -0,0 +1,197 @@
+// This file is part of pipecalc.
+//
+// pipecalc is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// pipecalc is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with pipecalc.  If not, see <http://www.gnu.org/licenses/>.
+
+use pipe::{Pipe, PipeError};
+use reed::ReedPipe;
+
+// Predicted pitch deviations of a flue and a reed rank at other temperatures than the
+// ones they were tuned at
+#[derive(Clone, PartialEq, Debug)]
+pub struct DriftReport {
+    // in C
+    temperatures: Vec<f64>,
+
+    // in cents, per temperature and pipe
+    flue: Vec<Vec<f64>>,
+    reed: Vec<Vec<f64>>,
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        None
+    } else {
+        Some(values.iter().sum::<f64>() / values.len() as f64)
+    }
+}
+
+impl DriftReport {
+    // Either rank may be empty
+    pub fn new(flue: &[Pipe], reed: &[ReedPipe], temperatures: &[f64]) -> Result<Self, PipeError> {
+        let mut report = DriftReport {
+            temperatures: temperatures.to_vec(),
+            flue: Vec::new(),
+            reed: Vec::new(),
+        };
+        for &temperature in temperatures {
+            report.flue.push(
+                flue.iter()
+                    .map(|pipe| pipe.get_temperature_drift(temperature))
+                    .collect::<Result<Vec<f64>, PipeError>>()?,
+            );
+            report.reed.push(
+                reed.iter()
+                    .map(|pipe| pipe.get_temperature_drift(temperature))
+                    .collect(),
+            );
+        }
+        Ok(report)
+    }
+
+    pub fn get_temperatures(&self) -> &[f64] {
+        &self.temperatures
+    }
+
+    // Deviation of every flue pipe at the temperature with the given index
+    pub fn get_flue_drift(&self, index: usize) -> &[f64] {
+        &self.flue[index]
+    }
+
+    pub fn get_reed_drift(&self, index: usize) -> &[f64] {
+        &self.reed[index]
+    }
+
+    // Mean deviation of the flue rank, None if it is empty
+    pub fn get_flue_mean(&self, index: usize) -> Option<f64> {
+        mean(&self.flue[index])
+    }
+
+    pub fn get_reed_mean(&self, index: usize) -> Option<f64> {
+        mean(&self.reed[index])
+    }
+
+    // How far the flue rank has moved away from the reed rank, in cents
+    pub fn get_detuning(&self, index: usize) -> Option<f64> {
+        match (self.get_flue_mean(index), self.get_reed_mean(index)) {
+            (Some(flue), Some(reed)) => Some(flue - reed),
+            _ => None,
+        }
+    }
+
+    // One line per temperature: temperature, tab, mean cents of the flue and reed ranks and
+    // their detuning, "-" for empty ranks
+    pub fn report(&self) -> String {
+        let format = |cents: Option<f64>| match cents {
+            Some(cents) => format!("{:+.1}", cents),
+            None => String::from("-"),
+        };
+
+        let mut report = String::from("Temperature\tFlue\tReed\tDetuning\n");
+        for (index, temperature) in self.temperatures.iter().enumerate() {
+            report.push_str(&format!(
+                "{:.1}\t{}\t{}\t{}\n",
+                temperature,
+                format(self.get_flue_mean(index)),
+                format(self.get_reed_mean(index)),
+                format(self.get_detuning(index))
+            ));
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::compass::PitchRange;
+    use super::super::temperament::{Pitch, Temperament};
+    use super::super::util::*;
+
+    #[test]
+    fn test_drift_report() {
+        let mut flue = c4_pipe();
+        flue.set_air_temperature(18.0);
+        let mut octave = flue.clone();
+        octave.set_frequency(523.25).set_radius(0.012);
+
+        let mut reed = ReedPipe::new();
+        reed.set_frequency(261.63).set_air_temperature(18.0);
+
+        let report =
+            DriftReport::new(&[flue, octave], &[reed.clone()], &[10.0, 18.0, 30.0]).unwrap();
+        assert_eq!(report.get_temperatures(), &[10.0, 18.0, 30.0]);
+        assert_eq!(report.get_flue_drift(0).len(), 2);
+        assert!(approx(report.get_flue_mean(1).unwrap(), 0.0, 0.000001));
+
+        // Flue pipes follow the speed of sound, reeds mostly the tongue
+        let flue_drift = 1200.0 * (speed_of_sound(30.0) / speed_of_sound(18.0)).log2();
+        assert!(approx(
+            report.get_flue_mean(2).unwrap(),
+            flue_drift,
+            0.000001,
+        ));
+        assert!(approx(
+            report.get_detuning(2).unwrap(),
+            0.8 * flue_drift,
+            0.000001,
+        ));
+        assert!(report.get_detuning(0).unwrap() < 0.0);
+
+        assert_eq!(
+            report.report(),
+            "Temperature\tFlue\tReed\tDetuning\n\
+             10.0\t-24.4\t-4.9\t-19.6\n\
+             18.0\t+0.0\t+0.0\t+0.0\n\
+             30.0\t+36.0\t+7.2\t+28.8\n"
+        );
+
+        let report = DriftReport::new(&[], &[reed], &[30.0]).unwrap();
+        assert_eq!(report.get_flue_mean(0), None);
+        assert_eq!(report.get_detuning(0), None);
+        assert_eq!(
+            report.report(),
+            "Temperature\tFlue\tReed\tDetuning\n30.0\t-\t+7.2\t-\n"
+        );
+    }
+
+    #[test]
+    fn test_full_compass_drift() {
+        let temperament = Temperament::new_default_equal();
+        let mut principal = Vec::new();
+        let mut gedackt = Vec::new();
+        for pitch in PitchRange::new(Pitch::from(0, 2), Pitch::from(0, 6)) {
+            let mut pipe = Pipe::new();
+            pipe.set_frequency(pitch.to_frequency(&temperament))
+                .set_radius(pitch.get_tnm_radius())
+                .set_mouth_ratio(0.25)
+                .set_cutup_ratio(0.25)
+                .set_air_density(1.2)
+                .set_wind_pressure(mmH2O_to_pa(70.0))
+                .set_air_temperature(18.0);
+            principal.push(pipe.clone());
+            pipe.set_stopped(true);
+            gedackt.push(pipe);
+        }
+
+        let flue_drift = 1200.0 * (speed_of_sound(30.0) / speed_of_sound(18.0)).log2();
+        for rank in &[principal, gedackt] {
+            let report = DriftReport::new(rank, &[], &[10.0, 30.0]).unwrap();
+            assert_eq!(report.get_flue_drift(1).len(), 49);
+            for &drift in report.get_flue_drift(1) {
+                assert!(approx(drift, flue_drift, 0.000001));
+            }
+            assert!(report.get_flue_mean(0).unwrap() < 0.0);
+        }
+    }
+}
@@ -14,9 +14,23 @@
 // along with pipecalc.  If not, see <http://www.gnu.org/licenses/>.
 
 mod temperament;
+mod compass;
+mod drift;
+mod midi;
+mod notation;
 mod pipe;
+mod reed;
+mod scala;
+mod scale;
 mod util;
 
 pub use temperament::*;
+pub use compass::*;
+pub use drift::*;
+pub use midi::*;
+pub use notation::*;
 pub use pipe::*;
+pub use reed::*;
+pub use scala::*;
+pub use scale::*;
 pub use util::*;
@@ -0,0 +1,144 @@
+// This file is part of pipecalc.
+//
+// pipecalc is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// pipecalc is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with pipecalc.  If not, see <http://www.gnu.org/licenses/>.
+
+use temperament::{Pitch, Temperament};
+
+// Octave of middle C (MIDI key 60) in scientific pitch notation
+pub const SCIENTIFIC_MIDDLE_C: i32 = 4;
+
+// Octave of middle C used by Yamaha and others
+pub const YAMAHA_MIDDLE_C: i32 = 3;
+
+impl Pitch {
+    pub fn from_midi(key: i32, middle_c_octave: i32) -> Self {
+        Pitch::from(key - 60, middle_c_octave)
+    }
+
+    // Nearest key, may lie outside of 0 - 127
+    pub fn to_midi(&self, middle_c_octave: i32) -> i32 {
+        60 + self.difference_to(&Pitch::from(0, middle_c_octave)).round() as i32
+    }
+}
+
+// Semitone and 14 bit fraction as used by the MIDI Tuning Standard,
+// 7F 7F 7F (no change) if out of range
+fn mts_frequency(frequency: f64) -> [u8; 3] {
+    let semitone = 69.0 + 12.0 * (frequency / 440.0).log2();
+    let mut key = semitone.floor();
+    let mut fraction = ((semitone - key) * 16384.0).round();
+    if fraction >= 16384.0 {
+        key += 1.0;
+        fraction = 0.0;
+    }
+
+    if !(0.0..=127.0).contains(&key) || (key == 127.0 && fraction > 16382.0) {
+        return [0x7F, 0x7F, 0x7F];
+    }
+
+    let fraction = fraction as u16;
+    [key as u8, (fraction >> 7) as u8, (fraction & 0x7F) as u8]
+}
+
+impl Temperament {
+    // Bulk tuning dump (F0 7E <device> 08 01 ...) retuning all 128 keys, the name is
+    // truncated or padded to 16 ASCII characters. EDO temperaments put consecutive steps on
+    // consecutive keys, step 0 of the base octave on its C
+    pub fn to_mts_bulk_dump(&self, device_id: u8, program: u8, name: &str) -> Vec<u8> {
+        let mut message = vec![0xF0, 0x7E, device_id & 0x7F, 0x08, 0x01, program & 0x7F];
+
+        let mut name: Vec<u8> = name
+            .bytes()
+            .map(|c| {
+                if c.is_ascii() && !c.is_ascii_control() {
+                    c
+                } else {
+                    b' '
+                }
+            })
+            .take(16)
+            .collect();
+        name.resize(16, b' ');
+        message.extend(name);
+
+        for key in 0..128 {
+            let frequency = match *self {
+                Temperament::EDO { base_octave, .. } => {
+                    let c = Pitch::from(0, base_octave).to_midi(SCIENTIFIC_MIDDLE_C);
+                    self.get_step_frequency(key - c, base_octave)
+                }
+                _ => Pitch::from_midi(key, SCIENTIFIC_MIDDLE_C).to_frequency(self),
+            };
+            message.extend(mts_frequency(frequency).iter());
+        }
+
+        let checksum = message[1..]
+            .iter()
+            .fold(0, |checksum, byte| checksum ^ byte);
+        message.push(checksum & 0x7F);
+        message.push(0xF7);
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_midi_keys() {
+        assert_eq!(Pitch::from(0, 4).to_midi(SCIENTIFIC_MIDDLE_C), 60);
+        assert_eq!(Pitch::from(9, 4).to_midi(SCIENTIFIC_MIDDLE_C), 69);
+        assert_eq!(Pitch::from(0, -1).to_midi(SCIENTIFIC_MIDDLE_C), 0);
+        assert_eq!(Pitch::from(0, 3).to_midi(YAMAHA_MIDDLE_C), 60);
+        assert_eq!(Pitch::from_with_cents(9, 4, 60.0).to_midi(4), 70);
+        assert_eq!(Pitch::from_with_cents(9, 4, -40.0).to_midi(4), 69);
+
+        assert_eq!(Pitch::from_midi(69, SCIENTIFIC_MIDDLE_C), Pitch::from(9, 4));
+        assert_eq!(Pitch::from_midi(21, YAMAHA_MIDDLE_C), Pitch::from(9, -1));
+        assert_eq!(Pitch::from_midi(127, 4), Pitch::from(7, 9));
+    }
+
+    #[test]
+    fn test_mts_bulk_dump() {
+        let dump = Temperament::new_default_equal().to_mts_bulk_dump(0x7F, 3, "Equal");
+        assert_eq!(dump.len(), 408);
+        assert_eq!(&dump[..6], &[0xF0, 0x7E, 0x7F, 0x08, 0x01, 0x03]);
+        assert_eq!(&dump[6..22], b"Equal           ");
+        assert_eq!(&dump[22 + 69 * 3..22 + 70 * 3], &[69, 0, 0]);
+        assert_eq!(&dump[22 + 60 * 3..22 + 61 * 3], &[60, 0, 0]);
+        assert_eq!(dump[407], 0xF7);
+
+        let checksum = dump[1..406]
+            .iter()
+            .fold(0, |checksum, byte| checksum ^ byte);
+        assert_eq!(dump[406], checksum & 0x7F);
+
+        // C is 11.73 cents above equal temperament relative to A in Werckmeister III
+        let dump = Temperament::new_default_werckmeister3().to_mts_bulk_dump(0, 0, "");
+        let fraction = (11.73 / 100.0 * 16384.0f64).round() as u16;
+        assert_eq!(
+            &dump[22 + 60 * 3..22 + 61 * 3],
+            &[60, (fraction >> 7) as u8, (fraction & 0x7F) as u8]
+        );
+
+        // 24-EDO: quarter tones on consecutive keys
+        let dump = Temperament::new_edo(24, 261.6256)
+            .unwrap()
+            .to_mts_bulk_dump(0, 0, "");
+        assert_eq!(&dump[22 + 60 * 3..22 + 61 * 3], &[60, 0, 0]);
+        assert_eq!(&dump[22 + 61 * 3..22 + 62 * 3], &[60, 64, 0]);
+        assert_eq!(&dump[22 + 84 * 3..22 + 85 * 3], &[72, 0, 0]);
+    }
+}
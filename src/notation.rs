@@ -0,0 +1,391 @@
+// This file is part of pipecalc.
+//
+// pipecalc is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// pipecalc is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with pipecalc.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use temperament::Pitch;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ParsePitchErrorKind {
+    Empty,
+    InvalidNoteName,
+    InvalidAccidental,
+    InvalidOctave,
+    InvalidCents,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ParsePitchError {
+    pub input: String,
+    pub kind: ParsePitchErrorKind,
+}
+
+impl fmt::Display for ParsePitchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self.kind {
+            ParsePitchErrorKind::Empty => "no pitch given",
+            ParsePitchErrorKind::InvalidNoteName => "expected a note name from A to H",
+            ParsePitchErrorKind::InvalidAccidental => "invalid accidental",
+            ParsePitchErrorKind::InvalidOctave => {
+                "expected an octave number or Helmholtz octave marks"
+            }
+            ParsePitchErrorKind::InvalidCents => "expected a cent offset like +12c",
+        };
+        write!(f, "Invalid pitch \"{}\": {}", self.input, reason)
+    }
+}
+
+impl Error for ParsePitchError {}
+
+// Splits a trailing cent offset like "+12c" or "-3.5c"
+fn split_cents(input: &str) -> Result<(&str, f64), ParsePitchErrorKind> {
+    if !input.ends_with('c') {
+        return Ok((input, 0.0));
+    }
+
+    let body = &input[..input.len() - 1];
+    if !body.ends_with(|c: char| c.is_ascii_digit() || c == '.') {
+        // A lone "c" is a note name
+        return Ok((input, 0.0));
+    }
+
+    match body.rfind(['+', '-']) {
+        Some(position) if position > 0 => match body[position..].parse() {
+            Ok(cents) => Ok((&body[..position], cents)),
+            Err(_) => Err(ParsePitchErrorKind::InvalidCents),
+        },
+        _ => Err(ParsePitchErrorKind::InvalidCents),
+    }
+}
+
+// Returns the semitone offset of the accidentals and the remaining input
+fn split_accidentals(input: &str, german: bool) -> (i32, &str) {
+    let mut offset = 0;
+    let mut rest = input;
+    loop {
+        if german && rest.starts_with("is") {
+            offset += 1;
+            rest = &rest[2..];
+        } else if german && rest.starts_with("es") {
+            offset -= 1;
+            rest = &rest[2..];
+        } else if german && rest.starts_with('s') {
+            // As, Es
+            offset -= 1;
+            rest = &rest[1..];
+        } else if rest.starts_with('#') {
+            offset += 1;
+            rest = &rest[1..];
+        } else if !german && rest.starts_with('b') {
+            offset -= 1;
+            rest = &rest[1..];
+        } else if rest.starts_with('\u{266F}') {
+            offset += 1;
+            rest = &rest['\u{266F}'.len_utf8()..];
+        } else if rest.starts_with('\u{266D}') {
+            offset -= 1;
+            rest = &rest['\u{266D}'.len_utf8()..];
+        } else {
+            return (offset, rest);
+        }
+    }
+}
+
+fn parse_octave(input: &str, lowercase: bool) -> Result<i32, ParsePitchErrorKind> {
+    if input.is_empty() || input.starts_with(['\'', ',']) {
+        // Helmholtz: C = C2, c = C3, c' = C4, C, = C1
+        let primes = input.chars().filter(|&c| c == '\'').count() as i32;
+        let commas = input.chars().filter(|&c| c == ',').count() as i32;
+        if primes + commas != input.chars().count() as i32 {
+            return Err(ParsePitchErrorKind::InvalidOctave);
+        }
+        return match (lowercase, primes, commas) {
+            (true, primes, 0) => Ok(3 + primes),
+            (false, 0, commas) => Ok(2 - commas),
+            _ => Err(ParsePitchErrorKind::InvalidOctave),
+        };
+    }
+
+    input
+        .parse()
+        .map_err(|_| ParsePitchErrorKind::InvalidOctave)
+}
+
+fn parse_pitch(input: &str, german: bool) -> Result<Pitch, ParsePitchErrorKind> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err(ParsePitchErrorKind::Empty);
+    }
+
+    let (input, cents) = split_cents(input)?;
+
+    let letter = input.chars().next().unwrap();
+    let note = match letter.to_ascii_uppercase() {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' if german => 10,
+        'B' | 'H' => 11,
+        _ => return Err(ParsePitchErrorKind::InvalidNoteName),
+    };
+
+    let (offset, rest) = split_accidentals(&input[letter.len_utf8()..], german);
+    if rest.starts_with(|c: char| c.is_alphabetic() || c == '#' || c == '\u{266D}') {
+        return Err(ParsePitchErrorKind::InvalidAccidental);
+    }
+
+    let octave = parse_octave(rest, letter.is_lowercase())?;
+    Ok(Pitch::from_with_cents(note + offset, octave, cents))
+}
+
+const SHARP_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+const FLAT_NAMES: [&str; 12] = [
+    "C", "Db", "D", "Eb", "E", "F", "Gb", "G", "Ab", "A", "Bb", "B",
+];
+const GERMAN_SHARP_NAMES: [&str; 12] = [
+    "C", "Cis", "D", "Dis", "E", "F", "Fis", "G", "Gis", "A", "B", "H",
+];
+const GERMAN_FLAT_NAMES: [&str; 12] = [
+    "C", "Des", "D", "Es", "E", "F", "Ges", "G", "As", "A", "B", "H",
+];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Notation {
+    // C4, A#3
+    Scientific,
+
+    // c', ais, C,
+    Helmholtz,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct PitchFormat {
+    notation: Notation,
+    german: bool,
+    flats: bool,
+    cents: bool,
+}
+
+impl PitchFormat {
+    pub fn new() -> PitchFormat {
+        PitchFormat {
+            notation: Notation::Scientific,
+            german: false,
+            flats: false,
+            cents: true,
+        }
+    }
+
+    pub fn set_notation(&mut self, notation: Notation) -> &mut Self {
+        self.notation = notation;
+        self
+    }
+
+    // H for B, B for Bb and suffixes like Fis or Es
+    pub fn set_german(&mut self, german: bool) -> &mut Self {
+        self.german = german;
+        self
+    }
+
+    pub fn set_flats(&mut self, flats: bool) -> &mut Self {
+        self.flats = flats;
+        self
+    }
+
+    // Appends offsets like +12c, zero offsets are never shown
+    pub fn set_cents(&mut self, cents: bool) -> &mut Self {
+        self.cents = cents;
+        self
+    }
+
+    // Note name without octave, upper case
+    pub fn format_name(&self, pitch: &Pitch) -> String {
+        let names = match (self.german, self.flats) {
+            (false, false) => &SHARP_NAMES,
+            (false, true) => &FLAT_NAMES,
+            (true, false) => &GERMAN_SHARP_NAMES,
+            (true, true) => &GERMAN_FLAT_NAMES,
+        };
+        names[pitch.get_note() as usize].to_string()
+    }
+
+    pub fn format(&self, pitch: &Pitch) -> String {
+        // Cents are shown to a tenth, rounding up to 100 moves on to the next note
+        let pitch = &Pitch::from_with_cents(
+            pitch.get_note(),
+            pitch.get_octave(),
+            (pitch.get_cents() * 10.0).round() / 10.0,
+        );
+        let name = self.format_name(pitch);
+        let octave = pitch.get_octave();
+
+        let mut formatted = match self.notation {
+            Notation::Scientific => format!("{}{}", name, octave),
+            Notation::Helmholtz if octave >= 3 => {
+                let primes = "'".repeat((octave - 3) as usize);
+                format!("{}{}", name.to_lowercase(), primes)
+            }
+            Notation::Helmholtz => format!("{}{}", name, ",".repeat((2 - octave) as usize)),
+        };
+
+        let cents = pitch.get_cents();
+        if self.cents && cents != 0.0 {
+            formatted.push_str(&format!("{:+}c", cents));
+        }
+        formatted
+    }
+}
+
+impl Default for PitchFormat {
+    fn default() -> PitchFormat {
+        PitchFormat::new()
+    }
+}
+
+impl fmt::Display for Pitch {
+    // Scientific notation with sharps, e.g. "C#4" or "A4+12c"
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", PitchFormat::new().format(self))
+    }
+}
+
+impl FromStr for Pitch {
+    type Err = ParsePitchError;
+
+    // Accepts "C#4", "Bb3", "c''", "C," and "H" for B, with an optional cent offset like "A4+12c"
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        parse_pitch(input, false).map_err(|kind| ParsePitchError {
+            input: input.to_string(),
+            kind,
+        })
+    }
+}
+
+impl Pitch {
+    // German spelling: B = Bb, H = B, accidentals as suffixes like "Fis" or "Es"
+    pub fn from_german_str(input: &str) -> Result<Self, ParsePitchError> {
+        parse_pitch(input, true).map_err(|kind| ParsePitchError {
+            input: input.to_string(),
+            kind,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_scientific() {
+        assert_eq!("C#4".parse::<Pitch>().unwrap(), Pitch::from(1, 4));
+        assert_eq!("Bb3".parse::<Pitch>().unwrap(), Pitch::from(10, 3));
+        assert_eq!("Cb4".parse::<Pitch>().unwrap(), Pitch::from(11, 3));
+        assert_eq!("g-1".parse::<Pitch>().unwrap(), Pitch::from(7, -1));
+        assert_eq!("H2".parse::<Pitch>().unwrap(), Pitch::from(11, 2));
+        assert_eq!(
+            "A4+12c".parse::<Pitch>().unwrap(),
+            Pitch::from_with_cents(9, 4, 12.0)
+        );
+        assert_eq!(
+            " F#2-3.5c ".parse::<Pitch>().unwrap(),
+            Pitch::from_with_cents(6, 2, -3.5)
+        );
+    }
+
+    #[test]
+    fn test_parse_helmholtz() {
+        assert_eq!("c''".parse::<Pitch>().unwrap(), Pitch::from(0, 5));
+        assert_eq!("c'".parse::<Pitch>().unwrap(), Pitch::from(0, 4));
+        assert_eq!("c".parse::<Pitch>().unwrap(), Pitch::from(0, 3));
+        assert_eq!("C".parse::<Pitch>().unwrap(), Pitch::from(0, 2));
+        assert_eq!("C,".parse::<Pitch>().unwrap(), Pitch::from(0, 1));
+        assert_eq!("C,,".parse::<Pitch>().unwrap(), Pitch::from(0, 0));
+        assert_eq!("f#'''".parse::<Pitch>().unwrap(), Pitch::from(6, 6));
+        assert_eq!(
+            "a'-5c".parse::<Pitch>().unwrap(),
+            Pitch::from_with_cents(9, 4, -5.0)
+        );
+
+        assert_eq!(Pitch::from_german_str("B").unwrap(), Pitch::from(10, 2));
+        assert_eq!(Pitch::from_german_str("h'").unwrap(), Pitch::from(11, 4));
+        assert_eq!(Pitch::from_german_str("fis''").unwrap(), Pitch::from(6, 5));
+        assert_eq!(Pitch::from_german_str("Es").unwrap(), Pitch::from(3, 2));
+        assert_eq!(Pitch::from_german_str("as").unwrap(), Pitch::from(8, 3));
+        assert_eq!(Pitch::from_german_str("Cis4").unwrap(), Pitch::from(1, 4));
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(Pitch::from(1, 4).to_string(), "C#4");
+        assert_eq!(Pitch::from(10, -1).to_string(), "A#-1");
+        assert_eq!(Pitch::from_with_cents(9, 4, 12.04).to_string(), "A4+12c");
+        assert_eq!(Pitch::from_with_cents(9, 4, -3.5).to_string(), "A4-3.5c");
+        assert_eq!(Pitch::from_with_cents(0, 4, 99.97).to_string(), "C#4");
+        assert_eq!(Pitch::from_with_cents(0, 4, -99.97).to_string(), "B3");
+        assert_eq!(Pitch::from_with_cents(0, 4, 99.94).to_string(), "C4+99.9c");
+
+        let mut format = PitchFormat::new();
+        format.set_flats(true).set_cents(false);
+        assert_eq!(format.format(&Pitch::from(10, 3)), "Bb3");
+        assert_eq!(format.format(&Pitch::from_with_cents(3, 3, 20.0)), "Eb3");
+
+        format.set_notation(Notation::Helmholtz);
+        assert_eq!(format.format(&Pitch::from(0, 5)), "c''");
+        assert_eq!(format.format(&Pitch::from(0, 3)), "c");
+        assert_eq!(format.format(&Pitch::from(0, 2)), "C");
+        assert_eq!(format.format(&Pitch::from(6, 0)), "Gb,,");
+
+        format.set_german(true);
+        assert_eq!(format.format(&Pitch::from(11, 4)), "h'");
+        assert_eq!(format.format(&Pitch::from(10, 1)), "B,");
+        assert_eq!(format.format(&Pitch::from(3, 3)), "es");
+        format.set_flats(false);
+        assert_eq!(format.format(&Pitch::from(6, 5)), "fis''");
+        assert_eq!(format.format_name(&Pitch::from(6, 5)), "Fis");
+
+        let mut helmholtz = PitchFormat::new();
+        helmholtz.set_notation(Notation::Helmholtz);
+        let mut german = PitchFormat::new();
+        german.set_german(true).set_flats(true);
+        for note in 0..60 {
+            let pitch = Pitch::from_with_cents(note, 0, 25.0);
+            assert_eq!(pitch.to_string().parse::<Pitch>().unwrap(), pitch);
+            assert_eq!(helmholtz.format(&pitch).parse::<Pitch>().unwrap(), pitch);
+            assert_eq!(
+                Pitch::from_german_str(&german.format(&pitch)).unwrap(),
+                pitch
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let kind = |input: &str| input.parse::<Pitch>().unwrap_err().kind;
+        assert_eq!(kind(""), ParsePitchErrorKind::Empty);
+        assert_eq!(kind("X4"), ParsePitchErrorKind::InvalidNoteName);
+        assert_eq!(kind("Cx4"), ParsePitchErrorKind::InvalidAccidental);
+        assert_eq!(kind("C'"), ParsePitchErrorKind::InvalidOctave);
+        assert_eq!(kind("c,"), ParsePitchErrorKind::InvalidOctave);
+        assert_eq!(kind("C4.5"), ParsePitchErrorKind::InvalidOctave);
+        assert_eq!(kind("C4+1.2.3c"), ParsePitchErrorKind::InvalidCents);
+    }
+}
@@ -13,18 +13,214 @@
 // You should have received a copy of the GNU General Public License
 // along with pipecalc.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::error::Error;
 use std::f64;
+use std::fmt;
 
-use util;
+use scale::PipeScale;
+use temperament::{Pitch, Temperament};
+use util::{self, SoundSpeedModel};
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Section {
+    // Cylindrical metal pipes, uses the radius of the pipe
+    Round,
+
+    // Wooden pipes, in m. The mouth is cut into the width side, mouth_ratio is relative
+    // to the width
+    Rectangular { width: f64, depth: f64 },
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Taper {
+    Cylindrical,
+    TopRadius(f64),
+    Ratio(f64),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PipeError {
+    MissingFrequency,
+    NonPositiveRadius,
+    InvalidTaper,
+
+    // A top radius on a rectangular pipe, or a taper combined with a chimney
+    UnsupportedTaper,
+
+    // The radius of a rectangular pipe is solved for or varied
+    UnsupportedSection,
+
+    InvalidChimney,
+    InvalidHarmonic,
+    NonPositiveIntonationNumber,
+    InvalidMouthRatio,
+    NonPositiveCutupRatio,
+    BelowAbsoluteZero,
+    NonPositiveAirDensity,
+    NonPositiveWindPressure,
+
+    // The end corrections exceed the theoretical length, the pipe is too wide for its pitch
+    NegativeResonatorLength,
+
+    // No value of the solved input gives the requested result
+    NoSolution,
+
+    // A Monte Carlo simulation without any valid sample
+    NoSamples,
+}
+
+impl fmt::Display for PipeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match *self {
+            PipeError::MissingFrequency => "no frequency given",
+            PipeError::NonPositiveRadius => "radius, width and depth must be positive",
+            PipeError::InvalidTaper => "the top radius and taper ratio must be positive",
+            PipeError::UnsupportedTaper => {
+                "rectangular pipes taper by ratio only and chimney pipes must be cylindrical"
+            }
+            PipeError::UnsupportedSection => "rectangular pipes have no radius",
+            PipeError::InvalidChimney => {
+                "the chimney length must not be negative and its radius must be positive"
+            }
+            PipeError::InvalidHarmonic => "the harmonic must be positive and odd for stopped pipes",
+            PipeError::NonPositiveIntonationNumber => "the intonation number must be positive",
+            PipeError::InvalidMouthRatio => "the mouth ratio must lie in (0, 1]",
+            PipeError::NonPositiveCutupRatio => "the cutup ratio must be positive",
+            PipeError::BelowAbsoluteZero => "the air temperature is below absolute zero",
+            PipeError::NonPositiveAirDensity => "the air density must be positive",
+            PipeError::NonPositiveWindPressure => "the wind pressure must be positive",
+            PipeError::NegativeResonatorLength => "the resulting resonator length is negative",
+            PipeError::NoSolution => "no solution for the requested value",
+            PipeError::NoSamples => "no valid samples",
+        };
+        write!(f, "Invalid pipe: {}", reason)
+    }
+}
+
+impl Error for PipeError {}
+
+// Desired output of Pipe::solve
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PipeTarget {
+    // in W
+    SoundPower(f64),
+
+    // in m
+    JetThickness(f64),
+
+    // in m^3/s
+    AirConsumptionRate(f64),
+}
+
+impl PipeTarget {
+    // (actual, wanted)
+    fn get_values(&self, dimensions: &PipeProperties) -> (f64, f64) {
+        match *self {
+            PipeTarget::SoundPower(wanted) => (dimensions.sound_power, wanted),
+            PipeTarget::JetThickness(wanted) => (dimensions.jet_thickness, wanted),
+            PipeTarget::AirConsumptionRate(wanted) => (dimensions.air_consumption_rate, wanted),
+        }
+    }
+}
+
+// Input solved for by Pipe::solve
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PipeParameter {
+    CutupRatio,
+    MouthRatio,
+    WindPressure,
+
+    // Round pipes only
+    Radius,
+}
+
+// Continuous input of Pipe::get_sensitivity and Pipe::get_tolerances
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PipeInput {
+    // Round pipes only
+    Radius,
+
+    MouthRatio,
+    CutupRatio,
+    IntonationNumber,
+    AirTemperature,
+    AirDensity,
+    WindPressure,
+}
+
+#[derive(Debug)]
+pub struct PipeSensitivity {
+    pub input: PipeInput,
+
+    // Partial derivative of every field with respect to the input, flags are those of the
+    // nominal pipe
+    pub derivatives: PipeProperties,
+
+    // in cents per unit of the input, for a pipe cut to the nominal resonator length
+    pub pitch: f64,
+}
+
+#[derive(Debug)]
+pub struct PipeTolerances {
+    // Flags are those of the nominal pipe
+    pub mean: PipeProperties,
+    pub standard_deviation: PipeProperties,
+
+    // in cents, for pipes cut to the nominal resonator length
+    pub pitch_mean: f64,
+    pub pitch_standard_deviation: f64,
+
+    // Samples with invalid inputs, e.g. a negative cutup ratio, are left out
+    pub valid_samples: usize,
+    pub invalid_samples: usize,
+}
+
+// xorshift64*, good enough to sample tolerances without pulling in a dependency
+struct Random {
+    state: u64,
+}
+
+impl Random {
+    fn new(seed: u64) -> Random {
+        Random { state: seed.max(1) }
+    }
+
+    // Uniform in (0, 1]
+    fn next_uniform(&mut self) -> f64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        let value = self.state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        ((value >> 11) + 1) as f64 / (1u64 << 53) as f64
+    }
+
+    // Standard normal, Box-Muller
+    fn next_normal(&mut self) -> f64 {
+        let radius = (-2.0 * self.next_uniform().ln()).sqrt();
+        radius * (2.0 * f64::consts::PI * self.next_uniform()).cos()
+    }
+}
+
+#[derive(Clone)]
 pub struct Pipe {
     stopped: bool,
+
+    // (length, radius) of the chimney of a half-stopped pipe, in m
+    chimney: Option<(f64, f64)>,
+
     frequency: f64,
+
+    // Harmonic the pipe speaks on, 1 for normal pipes
+    harmonic: u32,
+
     intonation_number: f64,
     radius: f64,
+    section: Section,
+    taper: Taper,
     mouth_ratio: f64,
     cutup_ratio: f64,
     air_temperature: f64,
+    sound_speed_model: SoundSpeedModel,
     air_density: f64,
     wind_pressure: f64,
 }
@@ -55,9 +251,12 @@ pub struct PipeProperties {
     // in m
     pub jet_thickness: f64,
 
-    // in m^2
+    // in m^2, at the mouth for conical pipes
     pub cross_section: f64,
 
+    // in m^2
+    pub top_cross_section: f64,
+
     // in m
     pub circumference: f64,
 
@@ -69,18 +268,65 @@ pub struct PipeProperties {
 
     // in m
     pub air_hole_diameter: f64,
+
+    // Pitch raise of a chimney compared to a fully stopped pipe of the same length, in cents
+    pub chimney_pitch_shift: f64,
+
+    // Distance of the node hole of harmonic pipes from the mouth, in m. 0 if not overblowing
+    pub node_hole_position: f64,
+
+    // in m, 0 if not overblowing
+    pub node_hole_diameter: f64,
+
+    // False if the intonation number is too low for the pipe to overblow reliably
+    pub stable_overblowing: bool,
+}
+
+impl PipeProperties {
+    // Applies f to every pair of numeric fields, flags are taken from self
+    fn combine<F: Fn(f64, f64) -> f64>(&self, other: &PipeProperties, f: F) -> PipeProperties {
+        PipeProperties {
+            resonator_length: f(self.resonator_length, other.resonator_length),
+            theoretical_resonator_length: f(
+                self.theoretical_resonator_length,
+                other.theoretical_resonator_length,
+            ),
+            mouth_height: f(self.mouth_height, other.mouth_height),
+            mouth_width: f(self.mouth_width, other.mouth_width),
+            mouth_area: f(self.mouth_area, other.mouth_area),
+            air_speed: f(self.air_speed, other.air_speed),
+            air_consumption_rate: f(self.air_consumption_rate, other.air_consumption_rate),
+            jet_thickness: f(self.jet_thickness, other.jet_thickness),
+            cross_section: f(self.cross_section, other.cross_section),
+            top_cross_section: f(self.top_cross_section, other.top_cross_section),
+            circumference: f(self.circumference, other.circumference),
+            pipe_depth: f(self.pipe_depth, other.pipe_depth),
+            sound_power: f(self.sound_power, other.sound_power),
+            air_hole_diameter: f(self.air_hole_diameter, other.air_hole_diameter),
+            chimney_pitch_shift: f(self.chimney_pitch_shift, other.chimney_pitch_shift),
+            node_hole_position: f(self.node_hole_position, other.node_hole_position),
+            node_hole_diameter: f(self.node_hole_diameter, other.node_hole_diameter),
+            stable_overblowing: self.stable_overblowing,
+        }
+    }
 }
 
 impl Pipe {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Pipe {
         Pipe {
             stopped: false,
+            chimney: None,
             frequency: 0.0,
+            harmonic: 1,
             intonation_number: 2.0,
             radius: 0.0,
+            section: Section::Round,
+            taper: Taper::Cylindrical,
             mouth_ratio: 0.25,
             cutup_ratio: 0.0,
             air_temperature: 20.0,
+            sound_speed_model: SoundSpeedModel::Linear,
             air_density: 0.0,
             wind_pressure: 0.0,
         }
@@ -91,8 +337,44 @@ impl Pipe {
         self
     }
 
+    // Makes the section round
     pub fn set_radius(&mut self, radius: f64) -> &mut Self {
         self.radius = radius;
+        self.section = Section::Round;
+        self
+    }
+
+    // Inner width and depth in m, makes the section rectangular
+    pub fn set_rectangular(&mut self, width: f64, depth: f64) -> &mut Self {
+        self.section = Section::Rectangular { width, depth };
+        self
+    }
+
+    // depth_ratio = depth / width
+    pub fn set_rectangular_ratio(&mut self, width: f64, depth_ratio: f64) -> &mut Self {
+        self.set_rectangular(width, width * depth_ratio)
+    }
+
+    pub fn set_scaled_radius<S: PipeScale>(&mut self, scale: &S, pitch: &Pitch) -> &mut Self {
+        self.set_radius(scale.get_radius(pitch))
+    }
+
+    // Conical round pipes like Spitzflöte or Gemshorn, the radius is the one at the mouth.
+    // A top wider than the mouth makes an inverted cone
+    pub fn set_top_radius(&mut self, top_radius: f64) -> &mut Self {
+        self.taper = Taper::TopRadius(top_radius);
+        self
+    }
+
+    // taper_ratio = top radius / mouth radius, or top width / mouth width for rectangular
+    // pipes tapering in both directions
+    pub fn set_taper_ratio(&mut self, taper_ratio: f64) -> &mut Self {
+        self.taper = Taper::Ratio(taper_ratio);
+        self
+    }
+
+    pub fn set_cylindrical(&mut self) -> &mut Self {
+        self.taper = Taper::Cylindrical;
         self
     }
 
@@ -106,8 +388,25 @@ impl Pipe {
         self
     }
 
+    // Removes the chimney of a half-stopped pipe
     pub fn set_stopped(&mut self, stopped: bool) -> &mut Self {
         self.stopped = stopped;
+        self.chimney = None;
+        self
+    }
+
+    // Half-stopped pipe (Rohrflöte), length and radius of the chimney in m.
+    // The body is treated as cylindrical
+    pub fn set_chimney(&mut self, length: f64, radius: f64) -> &mut Self {
+        self.stopped = true;
+        self.chimney = Some((length, radius));
+        self
+    }
+
+    // Harmonic flutes, usually 2 for open pipes. Stopped pipes only overblow to odd
+    // harmonics, try_get_dimensions rejects even ones
+    pub fn set_harmonic(&mut self, harmonic: u32) -> &mut Self {
+        self.harmonic = harmonic;
         self
     }
 
@@ -121,6 +420,11 @@ impl Pipe {
         self
     }
 
+    pub fn set_sound_speed_model(&mut self, sound_speed_model: SoundSpeedModel) -> &mut Self {
+        self.sound_speed_model = sound_speed_model;
+        self
+    }
+
     pub fn set_air_density(&mut self, air_density: f64) -> &mut Self {
         self.air_density = air_density;
         self
@@ -131,20 +435,458 @@ impl Pipe {
         self
     }
 
+    // (cross_section, circumference, mouth_width, pipe_depth)
+    fn get_section(&self) -> (f64, f64, f64, f64) {
+        match self.section {
+            Section::Round => {
+                let cross_section = f64::consts::PI * self.radius.powi(2);
+                let circumference = f64::consts::PI * self.radius * 2.0;
+                let mouth_width = circumference * self.mouth_ratio;
+                (
+                    cross_section,
+                    circumference,
+                    mouth_width,
+                    cross_section / mouth_width,
+                )
+            }
+            Section::Rectangular { width, depth } => (
+                width * depth,
+                2.0 * (width + depth),
+                width * self.mouth_ratio,
+                depth,
+            ),
+        }
+    }
+
+    fn get_top_cross_section(&self, cross_section: f64) -> f64 {
+        match (self.section, self.taper) {
+            (Section::Round, Taper::TopRadius(top_radius)) => f64::consts::PI * top_radius.powi(2),
+            (_, Taper::Ratio(ratio)) => cross_section * ratio.powi(2),
+            _ => cross_section,
+        }
+    }
+
+    // k * effective length of a stopped pipe. A cone closed at its narrow top resonates
+    // when tan(kL) = -k * x, x being the distance from the top to the apex of the cone.
+    // Pyramids are treated as cones of the same cross sections
+    fn get_stopped_phase(&self, cross_section: f64, top_cross_section: f64) -> f64 {
+        let ratio = (top_cross_section / cross_section).sqrt();
+        if ratio == 1.0 {
+            return f64::consts::FRAC_PI_2;
+        }
+
+        // x = q * L, negative for an inverted cone with its apex beyond the mouth. The root
+        // lies above pi / 2 for a narrowing and below for a widening cone
+        let q = ratio / (1.0 - ratio);
+        let (mut low, mut high) = if ratio < 1.0 {
+            (f64::consts::FRAC_PI_2, f64::consts::PI)
+        } else {
+            (0.0, f64::consts::FRAC_PI_2)
+        };
+        let rising = ratio > 1.0;
+        for _ in 0..64 {
+            let phase = (low + high) / 2.0;
+            if (phase.sin() + q * phase * phase.cos() > 0.0) != rising {
+                low = phase;
+            } else {
+                high = phase;
+            }
+        }
+        (low + high) / 2.0
+    }
+
+    // k * effective body length of a half-stopped pipe. Body and chimney resonate when
+    // tan(kL) = -(S / S_chimney) * tan(k * l_chimney)
+    fn get_chimney_phase(&self, wavelength: f64, cross_section: f64) -> f64 {
+        let (length, radius) = self.chimney.unwrap_or((0.0, 0.0));
+
+        // Unflanged outer end, inner end flanged by the cap
+        let effective_length = length + (0.6 + 0.82) * radius;
+        let k = 2.0 * f64::consts::PI / wavelength;
+        let load =
+            cross_section / (f64::consts::PI * radius.powi(2)) * (k * effective_length).tan();
+        if load >= 0.0 {
+            f64::consts::PI - load.atan()
+        } else {
+            -load.atan()
+        }
+    }
+
+    fn validate(&self) -> Result<(), PipeError> {
+        self.validate_resonator()?;
+
+        // Rejects NaN as well
+        let positive = |value: f64| value > 0.0 && value.is_finite();
+
+        if !positive(self.intonation_number) {
+            return Err(PipeError::NonPositiveIntonationNumber);
+        }
+        if !positive(self.air_density) {
+            return Err(PipeError::NonPositiveAirDensity);
+        }
+        if !positive(self.wind_pressure) {
+            return Err(PipeError::NonPositiveWindPressure);
+        }
+        Ok(())
+    }
+
+    // Only the inputs the resonator length depends on, the wind plays no part in it
+    fn validate_resonator(&self) -> Result<(), PipeError> {
+        // Rejects NaN as well
+        let positive = |value: f64| value > 0.0 && value.is_finite();
+
+        if !positive(self.frequency) {
+            return Err(PipeError::MissingFrequency);
+        }
+        let section_positive = match self.section {
+            Section::Round => positive(self.radius),
+            Section::Rectangular { width, depth } => positive(width) && positive(depth),
+        };
+        if !section_positive {
+            return Err(PipeError::NonPositiveRadius);
+        }
+        match self.taper {
+            Taper::TopRadius(value) | Taper::Ratio(value) if !positive(value) => {
+                return Err(PipeError::InvalidTaper)
+            }
+            Taper::TopRadius(_) if self.section != Section::Round => {
+                return Err(PipeError::UnsupportedTaper)
+            }
+            Taper::TopRadius(_) | Taper::Ratio(_) if self.chimney.is_some() => {
+                return Err(PipeError::UnsupportedTaper)
+            }
+            _ => {}
+        }
+        if let Some((length, radius)) = self.chimney {
+            if length.is_nan() || length < 0.0 || !positive(radius) {
+                return Err(PipeError::InvalidChimney);
+            }
+        }
+        if self.harmonic == 0 || (self.stopped && self.harmonic % 2 != 1) {
+            return Err(PipeError::InvalidHarmonic);
+        }
+        if !positive(self.mouth_ratio) || self.mouth_ratio > 1.0 {
+            return Err(PipeError::InvalidMouthRatio);
+        }
+        if !positive(self.cutup_ratio) {
+            return Err(PipeError::NonPositiveCutupRatio);
+        }
+        if self.air_temperature.is_nan() || self.air_temperature <= -273.15 {
+            return Err(PipeError::BelowAbsoluteZero);
+        }
+        Ok(())
+    }
+
+    // Like get_dimensions, but checks the inputs instead of returning NaN or infinite values
+    pub fn try_get_dimensions(&self) -> Result<PipeProperties, PipeError> {
+        self.validate()?;
+        let dimensions = self.get_dimensions();
+        if dimensions.resonator_length.is_nan() || dimensions.resonator_length <= 0.0 {
+            return Err(PipeError::NegativeResonatorLength);
+        }
+        Ok(dimensions)
+    }
+
+    // Frequency a pipe of the given resonator length in m sounds, ignores the set frequency
+    pub fn get_sounding_frequency(&self, resonator_length: f64) -> Result<f64, PipeError> {
+        if resonator_length.is_nan() || resonator_length <= 0.0 {
+            return Err(PipeError::NoSolution);
+        }
+
+        // Cylinder without end corrections, which lengthen the pipe and lower the estimate
+        let speed_of_sound = self
+            .sound_speed_model
+            .get_speed_of_sound(self.air_temperature);
+        let quarter_waves = if self.stopped { 1.0 } else { 2.0 };
+        let estimate =
+            f64::from(self.harmonic) * quarter_waves * speed_of_sound / (4.0 * resonator_length);
+
+        let mut pipe = self.clone();
+        pipe.set_frequency(estimate).validate_resonator()?;
+        let mut length_error = |frequency: f64| {
+            pipe.set_frequency(frequency);
+            pipe.validate_resonator()
+                .map(|_| pipe.get_dimensions().resonator_length - resonator_length)
+        };
+
+        // The resonator length decreases with the frequency
+        let mut low = estimate / 2.0;
+        let mut high = estimate * 2.0;
+        for _ in 0..64 {
+            if length_error(low)? > 0.0 {
+                break;
+            }
+            low /= 2.0;
+        }
+        for _ in 0..64 {
+            if length_error(high)? < 0.0 {
+                break;
+            }
+            high *= 2.0;
+        }
+        if !(length_error(low)? > 0.0 && length_error(high)? < 0.0) {
+            return Err(PipeError::NoSolution);
+        }
+
+        for _ in 0..100 {
+            let frequency = (low * high).sqrt();
+            if length_error(frequency)? > 0.0 {
+                low = frequency;
+            } else {
+                high = frequency;
+            }
+        }
+        Ok((low * high).sqrt())
+    }
+
+    fn set_parameter(&mut self, parameter: PipeParameter, value: f64) -> &mut Self {
+        match parameter {
+            PipeParameter::CutupRatio => self.set_cutup_ratio(value),
+            PipeParameter::MouthRatio => self.set_mouth_ratio(value),
+            PipeParameter::WindPressure => self.set_wind_pressure(value),
+            PipeParameter::Radius => self.set_radius(value),
+        }
+    }
+
+    // Value of parameter giving the target with all other inputs as set. The search
+    // starts from the set value of parameter, or a typical one if it is not set
+    pub fn solve(&self, target: PipeTarget, parameter: PipeParameter) -> Result<f64, PipeError> {
+        if parameter == PipeParameter::Radius && self.section != Section::Round {
+            return Err(PipeError::UnsupportedSection);
+        }
+
+        let (start, upper) = match parameter {
+            PipeParameter::CutupRatio => (self.cutup_ratio, f64::INFINITY),
+            PipeParameter::MouthRatio => (self.mouth_ratio, 1.0),
+            PipeParameter::WindPressure => (self.wind_pressure, f64::INFINITY),
+            PipeParameter::Radius => (self.radius, f64::INFINITY),
+        };
+        let start = if start > 0.0 && start <= upper {
+            start
+        } else {
+            match parameter {
+                PipeParameter::CutupRatio | PipeParameter::MouthRatio => 0.25,
+                PipeParameter::WindPressure => util::mmH2O_to_pa(60.0),
+                PipeParameter::Radius => 0.02,
+            }
+        };
+
+        let mut pipe = self.clone();
+        let mut log_error = |value: f64| -> Result<f64, PipeError> {
+            let dimensions = pipe.set_parameter(parameter, value).try_get_dimensions()?;
+            let (actual, wanted) = target.get_values(&dimensions);
+            let error = (actual / wanted).ln();
+            if error.is_nan() {
+                Err(PipeError::NoSolution)
+            } else {
+                Ok(error)
+            }
+        };
+
+        // Widen the bracket in both directions as long as the pipe stays valid
+        let (mut low, mut high) = (start, start);
+        let mut low_error = log_error(start)?;
+        let mut high_error = low_error;
+        for _ in 0..64 {
+            if low_error.signum() != high_error.signum() {
+                break;
+            }
+            if let Ok(error) = log_error(low / 2.0) {
+                low /= 2.0;
+                low_error = error;
+            }
+            let next = (high * 2.0).min(upper);
+            if let Ok(error) = log_error(next) {
+                high = next;
+                high_error = error;
+            }
+        }
+        if low_error.signum() == high_error.signum() {
+            return Err(PipeError::NoSolution);
+        }
+
+        for _ in 0..100 {
+            let value = (low * high).sqrt();
+            if log_error(value)?.signum() == low_error.signum() {
+                low = value;
+            } else {
+                high = value;
+            }
+        }
+        Ok((low * high).sqrt())
+    }
+
+    fn get_input(&self, input: PipeInput) -> f64 {
+        match input {
+            PipeInput::Radius => self.radius,
+            PipeInput::MouthRatio => self.mouth_ratio,
+            PipeInput::CutupRatio => self.cutup_ratio,
+            PipeInput::IntonationNumber => self.intonation_number,
+            PipeInput::AirTemperature => self.air_temperature,
+            PipeInput::AirDensity => self.air_density,
+            PipeInput::WindPressure => self.wind_pressure,
+        }
+    }
+
+    fn set_input(&mut self, input: PipeInput, value: f64) -> &mut Self {
+        match input {
+            PipeInput::Radius => self.set_radius(value),
+            PipeInput::MouthRatio => self.set_mouth_ratio(value),
+            PipeInput::CutupRatio => self.set_cutup_ratio(value),
+            PipeInput::IntonationNumber => self.set_intonation_number(value),
+            PipeInput::AirTemperature => self.set_air_temperature(value),
+            PipeInput::AirDensity => self.set_air_density(value),
+            PipeInput::WindPressure => self.set_wind_pressure(value),
+        }
+    }
+
+    // Deviation in cents of this pipe cut to resonator_length from the set frequency
+    fn get_pitch_deviation(&self, resonator_length: f64) -> Result<f64, PipeError> {
+        self.get_sounding_frequency(resonator_length)
+            .map(|frequency| 1200.0 * (frequency / self.frequency).log2())
+    }
+
+    fn check_input(&self, input: PipeInput) -> Result<(), PipeError> {
+        if input == PipeInput::Radius && self.section != Section::Round {
+            return Err(PipeError::UnsupportedSection);
+        }
+        Ok(())
+    }
+
+    // Central differences, one-sided at the limits of the input like a mouth ratio of 1.
+    // The step is 1e-6, relative to the value of inputs above 1
+    pub fn get_sensitivity(&self, input: PipeInput) -> Result<PipeSensitivity, PipeError> {
+        self.check_input(input)?;
+        let nominal = self.try_get_dimensions()?;
+        let value = self.get_input(input);
+        let step = 1e-6 * value.abs().max(1.0);
+
+        // Dimensions and pitch deviation with the input moved by offset
+        let evaluate = |offset: f64| -> Result<(PipeProperties, f64), PipeError> {
+            let mut pipe = self.clone();
+            pipe.set_input(input, value + offset);
+            let dimensions = pipe.try_get_dimensions()?;
+            let pitch = pipe.get_pitch_deviation(nominal.resonator_length)?;
+            Ok((dimensions, pitch))
+        };
+        let ((above, pitch_above), (below, pitch_below), width) =
+            match (evaluate(step), evaluate(-step)) {
+                (Ok(above), Ok(below)) => (above, below, 2.0 * step),
+                (Ok(above), Err(_)) => (above, evaluate(0.0)?, step),
+                (Err(_), Ok(below)) => (evaluate(0.0)?, below, step),
+                (Err(error), Err(_)) => return Err(error),
+            };
+
+        let derivatives = above.combine(&below, |above, below| (above - below) / width);
+        let pitch = (pitch_above - pitch_below) / width;
+
+        Ok(PipeSensitivity {
+            input,
+            derivatives: nominal.combine(&derivatives, |_, derivative| derivative),
+            pitch,
+        })
+    }
+
+    // Monte Carlo simulation with normally distributed inputs, given as (input, standard
+    // deviation). The same seed gives the same result
+    pub fn get_tolerances(
+        &self,
+        deviations: &[(PipeInput, f64)],
+        samples: usize,
+        seed: u64,
+    ) -> Result<PipeTolerances, PipeError> {
+        for &(input, _) in deviations {
+            self.check_input(input)?;
+        }
+        let nominal = self.try_get_dimensions()?;
+        let mut random = Random::new(seed);
+
+        let mut sum = nominal.combine(&nominal, |_, _| 0.0);
+        let mut sum_squares = nominal.combine(&nominal, |_, _| 0.0);
+        let (mut pitch_sum, mut pitch_sum_squares) = (0.0, 0.0);
+        let mut valid_samples = 0;
+        for _ in 0..samples {
+            let mut pipe = self.clone();
+            for &(input, deviation) in deviations {
+                let value = self.get_input(input) + deviation * random.next_normal();
+                pipe.set_input(input, value);
+            }
+
+            let (dimensions, pitch) = match (
+                pipe.try_get_dimensions(),
+                pipe.get_pitch_deviation(nominal.resonator_length),
+            ) {
+                (Ok(dimensions), Ok(pitch)) => (dimensions, pitch),
+                _ => continue,
+            };
+            valid_samples += 1;
+            sum = sum.combine(&dimensions, |sum, value| sum + value);
+            sum_squares = sum_squares.combine(&dimensions, |sum, value| sum + value * value);
+            pitch_sum += pitch;
+            pitch_sum_squares += pitch * pitch;
+        }
+        if valid_samples == 0 {
+            return Err(PipeError::NoSamples);
+        }
+
+        let n = valid_samples as f64;
+        let mean = sum.combine(&sum, |sum, _| sum / n);
+        let standard_deviation = sum_squares.combine(&mean, |sum_squares, mean| {
+            (sum_squares / n - mean * mean).max(0.0).sqrt()
+        });
+        let pitch_mean = pitch_sum / n;
+
+        Ok(PipeTolerances {
+            mean: nominal.combine(&mean, |_, mean| mean),
+            standard_deviation: nominal.combine(&standard_deviation, |_, deviation| deviation),
+            pitch_mean,
+            pitch_standard_deviation: (pitch_sum_squares / n - pitch_mean * pitch_mean)
+                .max(0.0)
+                .sqrt(),
+            valid_samples,
+            invalid_samples: samples - valid_samples,
+        })
+    }
+
+    // Deviation in cents at another air temperature of this pipe cut for the set one
+    // The end corrections do not depend on the temperature, but the chimney phase does
+    pub fn get_temperature_drift(&self, air_temperature: f64) -> Result<f64, PipeError> {
+        let resonator_length = self.try_get_dimensions()?.resonator_length;
+        let nominal = self.get_sounding_frequency(resonator_length)?;
+        let frequency = self
+            .clone()
+            .set_air_temperature(air_temperature)
+            .get_sounding_frequency(resonator_length)?;
+        Ok(1200.0 * (frequency / nominal).log2())
+    }
+
+    // Nearest pitch of the temperament and the deviation from it in cents
+    pub fn get_sounding_pitch(
+        &self,
+        resonator_length: f64,
+        temperament: &Temperament,
+    ) -> Result<(Pitch, f64), PipeError> {
+        self.get_sounding_frequency(resonator_length)
+            .and_then(|frequency| {
+                temperament
+                    .pitch_for_frequency(frequency)
+                    .ok_or(PipeError::NoSolution)
+            })
+    }
+
     // air_temperature in C, air_density in kg/m^3, wind_pressure in Pa
     pub fn get_dimensions(&self) -> PipeProperties {
-        let speed_of_sound = util::speed_of_sound(self.air_temperature); // in m/s
+        let speed_of_sound = self
+            .sound_speed_model
+            .get_speed_of_sound(self.air_temperature); // in m/s
         let air_speed = util::air_speed(self.wind_pressure, self.air_density); // in m/s
 
         let i = self.intonation_number;
         let f = self.frequency; // in Hz
 
-        let cross_section = f64::consts::PI * self.radius.powi(2);
-        let circumference = f64::consts::PI * self.radius * 2.0;
-        let mouth_width = circumference * self.mouth_ratio;
+        let (cross_section, circumference, mouth_width, pipe_depth) = self.get_section();
         let mouth_height = mouth_width * self.cutup_ratio;
         let mouth_area = mouth_height * mouth_width;
-        let pipe_depth = cross_section / mouth_width;
 
         let jet_thickness =
             0.001 * (f.powi(2) * i.powi(2) * (10.0 * mouth_height).powi(3)) / air_speed.powi(2);
@@ -156,22 +898,52 @@ impl Pipe {
         let air_consumption_rate = air_speed * mouth_width * jet_thickness;
 
         let wavelength = speed_of_sound / f;
+        let top_cross_section = self.get_top_cross_section(cross_section);
+
+        // End corrections at the mouth and at the open top
+        let mouth_correction = 0.73 * cross_section / mouth_area.sqrt();
+        let top_correction = 0.34 * top_cross_section.sqrt();
+
+        let stopped_phase = if self.chimney.is_some() {
+            self.get_chimney_phase(wavelength, cross_section)
+        } else {
+            self.get_stopped_phase(cross_section, top_cross_section)
+        };
+        let chimney_pitch_shift = if self.chimney.is_some() {
+            1200.0 * (2.0 * stopped_phase / f64::consts::PI).log2()
+        } else {
+            0.0
+        };
+
+        let harmonic = f64::from(self.harmonic);
+
+        // Each further mode adds half a wavelength
         let theoretical_resonator_length = if self.stopped {
-            wavelength / 4.0
+            (stopped_phase + (harmonic - 1.0) * f64::consts::FRAC_PI_2) / (2.0 * f64::consts::PI)
+                * wavelength
         } else {
-            wavelength / 2.0
+            harmonic * wavelength / 2.0
         };
 
         let resonator_length = if self.stopped {
-            (-0.73 * (f * cross_section - 0.342466 * speed_of_sound * mouth_area.sqrt()))
-                / (f * mouth_area.sqrt())
+            theoretical_resonator_length - mouth_correction
         } else {
-            (-0.73
-                * (f * cross_section + 0.465753 * f * mouth_area.sqrt() * cross_section.sqrt()
-                    - 0.684932 * speed_of_sound * mouth_area.sqrt()))
-                / (f * mouth_area.sqrt())
+            theoretical_resonator_length - mouth_correction - top_correction
         };
 
+        // The hole sits at the first pressure node, suppressing the fundamental
+        let (node_hole_position, node_hole_diameter) = if self.harmonic > 1 {
+            (
+                wavelength / 2.0 - mouth_correction,
+                0.2 * 2.0 * (cross_section / f64::consts::PI).sqrt(),
+            )
+        } else {
+            (0.0, 0.0)
+        };
+
+        // Rule of thumb, overblowing needs a stronger jet with every harmonic
+        let stable_overblowing = self.harmonic == 1 || i >= harmonic + 1.0;
+
         let air_hole_diameter =
             2.0 * ((mouth_width * jet_thickness * 10.0).sqrt() / f64::consts::PI);
 
@@ -185,10 +957,15 @@ impl Pipe {
             air_consumption_rate,
             jet_thickness,
             cross_section,
+            top_cross_section,
             circumference,
             pipe_depth,
             sound_power,
             air_hole_diameter,
+            chimney_pitch_shift,
+            node_hole_position,
+            node_hole_diameter,
+            stable_overblowing,
         }
     }
 }
@@ -242,4 +1019,482 @@ mod tests {
         assert!(approx(dimensions.cross_section, 0.000830712, 0.00001));
         assert!(approx(dimensions.circumference, 0.102171, 0.001));
     }
+
+    #[test]
+    fn test_rectangular_pipe() {
+        let mut pipe = Pipe::new();
+        pipe.set_frequency(130.81)
+            .set_rectangular(0.06, 0.075)
+            .set_mouth_ratio(1.0)
+            .set_cutup_ratio(0.3)
+            .set_air_density(1.2)
+            .set_wind_pressure(util::mmH2O_to_pa(70.0));
+        let dimensions = pipe.get_dimensions();
+        assert!(approx(dimensions.cross_section, 0.0045, 0.000001));
+        assert!(approx(dimensions.circumference, 0.27, 0.000001));
+        assert!(approx(dimensions.mouth_width, 0.06, 0.000001));
+        assert!(approx(dimensions.mouth_height, 0.018, 0.000001));
+        assert!(approx(dimensions.pipe_depth, 0.075, 0.000001));
+
+        // Same cross section and mouth as a round pipe gives the same length
+        let radius = (0.0045 / f64::consts::PI).sqrt();
+        pipe.set_radius(radius)
+            .set_mouth_ratio(0.06 / (2.0 * f64::consts::PI * radius));
+        let round = pipe.get_dimensions();
+        assert!(approx(round.mouth_area, dimensions.mouth_area, 0.000001));
+        assert!(approx(
+            round.resonator_length,
+            dimensions.resonator_length,
+            0.000001,
+        ));
+
+        pipe.set_rectangular_ratio(0.06, 1.25);
+        assert!(approx(pipe.get_dimensions().pipe_depth, 0.075, 0.000001));
+    }
+
+    #[test]
+    fn test_conical_pipe() {
+        let mut pipe = c4_pipe();
+        let cylinder = pipe.get_dimensions();
+
+        // Spitzflöte with a third of the mouth diameter at the top
+        pipe.set_top_radius(0.02 / 3.0);
+        let cone = pipe.get_dimensions();
+        assert!(approx(
+            cone.top_cross_section,
+            cylinder.cross_section / 9.0,
+            0.0000001,
+        ));
+        assert!(approx(cone.mouth_area, cylinder.mouth_area, 0.0000001));
+        assert!(approx(
+            cone.resonator_length - cylinder.resonator_length,
+            0.34 * (cylinder.cross_section.sqrt() - cone.top_cross_section.sqrt()),
+            0.0000001,
+        ));
+        pipe.set_taper_ratio(1.0 / 3.0);
+        assert!(approx(
+            pipe.get_dimensions().resonator_length,
+            cone.resonator_length,
+            0.0000001,
+        ));
+
+        // Stopped cone with half the diameter at the top: tan(kL) = -kL
+        pipe.set_stopped(true).set_taper_ratio(0.5);
+        let stopped_cone = pipe.get_dimensions();
+        pipe.set_cylindrical();
+        let stopped = pipe.get_dimensions();
+        assert!(approx(
+            stopped_cone.theoretical_resonator_length / stopped.theoretical_resonator_length,
+            2.028758 / f64::consts::FRAC_PI_2,
+            0.00001,
+        ));
+        assert!(stopped_cone.resonator_length > stopped.resonator_length);
+
+        // Inverted stopped cone with twice the diameter at the top: tan(kL) = 2kL
+        pipe.set_taper_ratio(2.0);
+        let inverted = pipe.get_dimensions();
+        assert!(approx(
+            inverted.theoretical_resonator_length / stopped.theoretical_resonator_length,
+            1.165561 / f64::consts::FRAC_PI_2,
+            0.00001,
+        ));
+
+        // Tapered wooden pipe
+        pipe.set_rectangular(0.03, 0.04);
+        let pyramid = pipe.get_dimensions();
+        assert!(approx(
+            pyramid.top_cross_section,
+            0.03 * 0.04 * 4.0,
+            0.0000001,
+        ));
+        assert!(pipe.try_get_dimensions().is_ok());
+        pipe.set_top_radius(0.01);
+        assert_eq!(
+            pipe.try_get_dimensions().unwrap_err(),
+            PipeError::UnsupportedTaper
+        );
+
+        pipe.set_radius(0.02).set_chimney(0.04, 0.005);
+        assert_eq!(
+            pipe.try_get_dimensions().unwrap_err(),
+            PipeError::UnsupportedTaper
+        );
+        pipe.set_cylindrical();
+        assert!(pipe.try_get_dimensions().is_ok());
+    }
+
+    #[test]
+    fn test_chimney_pipe() {
+        let mut pipe = c4_pipe();
+        pipe.set_stopped(true);
+        let stopped = pipe.get_dimensions();
+        assert_eq!(stopped.chimney_pitch_shift, 0.0);
+
+        pipe.set_chimney(0.04, 0.005);
+        let chimney = pipe.get_dimensions();
+        assert!(chimney.resonator_length > stopped.resonator_length);
+        assert!(chimney.chimney_pitch_shift > 0.0);
+        assert!(approx(
+            stopped.theoretical_resonator_length * (chimney.chimney_pitch_shift / 1200.0).exp2(),
+            chimney.theoretical_resonator_length,
+            0.0000001,
+        ));
+
+        // A chimney as wide as the body continues it like an open pipe
+        let wavelength = util::speed_of_sound(20.0) / 261.63;
+        pipe.set_chimney(0.1, 0.02);
+        let open = pipe.get_dimensions();
+        assert!(approx(
+            open.theoretical_resonator_length + 0.1 + 1.42 * 0.02,
+            wavelength / 2.0,
+            0.0000001,
+        ));
+
+        pipe.set_stopped(true);
+        assert_eq!(pipe.get_dimensions().chimney_pitch_shift, 0.0);
+    }
+
+    #[test]
+    fn test_harmonic_pipe() {
+        let mut pipe = Pipe::new();
+        pipe.set_frequency(523.25)
+            .set_radius(0.012)
+            .set_mouth_ratio(0.25)
+            .set_cutup_ratio(0.25)
+            .set_air_density(1.2)
+            .set_wind_pressure(util::mmH2O_to_pa(80.0));
+        let normal = pipe.get_dimensions();
+        assert_eq!(normal.node_hole_position, 0.0);
+        assert!(normal.stable_overblowing);
+
+        pipe.set_harmonic(2);
+        let harmonic = pipe.get_dimensions();
+        assert!(approx(
+            harmonic.theoretical_resonator_length,
+            2.0 * normal.theoretical_resonator_length,
+            0.0000001,
+        ));
+        assert!(approx(
+            harmonic.node_hole_position,
+            normal.theoretical_resonator_length
+                - 0.73 * harmonic.cross_section / harmonic.mouth_area.sqrt(),
+            0.0000001,
+        ));
+        assert!(approx(harmonic.node_hole_diameter, 0.0048, 0.0000001));
+        assert!(!harmonic.stable_overblowing);
+
+        pipe.set_intonation_number(3.0);
+        assert!(pipe.get_dimensions().stable_overblowing);
+
+        // Stopped pipes speak on the twelfth
+        pipe.set_stopped(true).set_harmonic(3);
+        let stopped = pipe.get_dimensions();
+        assert!(approx(
+            stopped.theoretical_resonator_length,
+            3.0 / 4.0 * util::speed_of_sound(20.0) / 523.25,
+            0.0000001,
+        ));
+
+        pipe.set_harmonic(0);
+        assert_eq!(
+            pipe.try_get_dimensions().unwrap_err(),
+            PipeError::InvalidHarmonic
+        );
+    }
+
+    #[test]
+    fn test_pipe_errors() {
+        let mut pipe = Pipe::new();
+        assert_eq!(
+            pipe.try_get_dimensions().unwrap_err(),
+            PipeError::MissingFrequency
+        );
+
+        pipe.set_frequency(261.63);
+        assert_eq!(
+            pipe.try_get_dimensions().unwrap_err(),
+            PipeError::NonPositiveRadius
+        );
+
+        pipe.set_radius(0.02)
+            .set_mouth_ratio(1.5)
+            .set_cutup_ratio(0.25);
+        assert_eq!(
+            pipe.try_get_dimensions().unwrap_err(),
+            PipeError::InvalidMouthRatio
+        );
+
+        pipe.set_mouth_ratio(0.25);
+        assert_eq!(
+            pipe.try_get_dimensions().unwrap_err(),
+            PipeError::NonPositiveAirDensity
+        );
+
+        pipe.set_air_density(1.2)
+            .set_wind_pressure(util::mmH2O_to_pa(60.0));
+        assert!(pipe.try_get_dimensions().is_ok());
+
+        pipe.set_stopped(true).set_harmonic(2);
+        assert_eq!(
+            pipe.try_get_dimensions().unwrap_err(),
+            PipeError::InvalidHarmonic
+        );
+
+        // Far too wide for its pitch
+        pipe.set_harmonic(1).set_frequency(8000.0).set_radius(0.1);
+        let error = pipe.try_get_dimensions().unwrap_err();
+        assert_eq!(error, PipeError::NegativeResonatorLength);
+        assert_eq!(
+            error.to_string(),
+            "Invalid pipe: the resulting resonator length is negative"
+        );
+    }
+
+    #[test]
+    fn test_sounding_frequency() {
+        let temp = Temperament::new_default_equal();
+        let mut pipe = c4_pipe();
+        let length = pipe.get_dimensions().resonator_length;
+        assert!(approx(
+            pipe.get_sounding_frequency(length).unwrap(),
+            261.63,
+            0.0001,
+        ));
+
+        // Measuring an old pipe needs neither wind nor frequency
+        let mut old = Pipe::new();
+        old.set_radius(0.02).set_cutup_ratio(0.25);
+        assert!(approx(
+            old.get_sounding_frequency(length).unwrap(),
+            261.63,
+            0.0001,
+        ));
+
+        // A pipe cut 1 cm too short sounds sharp
+        let (pitch, cents) = pipe.get_sounding_pitch(length - 0.01, &temp).unwrap();
+        assert_eq!(pitch, Pitch::from(0, 4));
+        assert!(cents > 20.0 && cents < 30.0);
+
+        pipe.set_chimney(0.04, 0.005);
+        let length = pipe.get_dimensions().resonator_length;
+        assert!(approx(
+            pipe.get_sounding_frequency(length).unwrap(),
+            261.63,
+            0.0001,
+        ));
+
+        assert_eq!(
+            pipe.get_sounding_frequency(-0.1).unwrap_err(),
+            PipeError::NoSolution
+        );
+
+        // 8' C principal, its end corrections exceed half a wavelength at 1000 Hz
+        let mut pipe = Pipe::new();
+        pipe.set_frequency(65.406)
+            .set_radius(Pitch::from(0, 2).get_tnm_radius())
+            .set_mouth_ratio(0.25)
+            .set_cutup_ratio(0.25)
+            .set_air_density(1.2)
+            .set_wind_pressure(util::mmH2O_to_pa(70.0));
+        let length = pipe.try_get_dimensions().unwrap().resonator_length;
+        assert!(approx(
+            pipe.get_sounding_frequency(length).unwrap(),
+            65.406,
+            0.0001,
+        ));
+        assert!(approx(
+            pipe.get_sounding_frequency(2.35).unwrap(),
+            65.406,
+            0.5,
+        ));
+
+        pipe.set_stopped(true);
+        let length = pipe.try_get_dimensions().unwrap().resonator_length;
+        assert!(approx(
+            pipe.get_sounding_frequency(length).unwrap(),
+            65.406,
+            0.0001,
+        ));
+    }
+
+    #[test]
+    fn test_solve() {
+        let pipe = c4_pipe();
+        let dimensions = pipe.get_dimensions();
+
+        // Recover each input from an output of the original pipe
+        let targets = [
+            PipeTarget::SoundPower(dimensions.sound_power * 2.0),
+            PipeTarget::JetThickness(dimensions.jet_thickness * 2.0),
+            PipeTarget::AirConsumptionRate(dimensions.air_consumption_rate * 2.0),
+        ];
+        let parameters = [
+            PipeParameter::CutupRatio,
+            PipeParameter::MouthRatio,
+            PipeParameter::WindPressure,
+            PipeParameter::Radius,
+        ];
+        for target in &targets {
+            for parameter in &parameters {
+                let value = pipe.solve(*target, *parameter).unwrap();
+                let mut solved = pipe.clone();
+                let (actual, wanted) =
+                    target.get_values(&solved.set_parameter(*parameter, value).get_dimensions());
+                assert!(approx(actual / wanted, 1.0, 0.000001));
+            }
+        }
+
+        // Louder by raising the cutup
+        let cutup = pipe
+            .solve(
+                PipeTarget::SoundPower(dimensions.sound_power * 2.0),
+                PipeParameter::CutupRatio,
+            )
+            .unwrap();
+        assert!(cutup > 0.25);
+
+        // The mouth ratio cannot exceed 1
+        assert_eq!(
+            pipe.solve(
+                PipeTarget::JetThickness(dimensions.jet_thickness * 1000.0),
+                PipeParameter::MouthRatio,
+            )
+            .unwrap_err(),
+            PipeError::NoSolution
+        );
+        let mut wooden = pipe.clone();
+        wooden.set_rectangular(0.03, 0.04);
+        assert_eq!(
+            wooden
+                .solve(
+                    PipeTarget::SoundPower(dimensions.sound_power),
+                    PipeParameter::Radius,
+                )
+                .unwrap_err(),
+            PipeError::UnsupportedSection
+        );
+    }
+
+    #[test]
+    fn test_sensitivity() {
+        let mut pipe = c4_pipe();
+        let nominal = pipe.get_dimensions();
+
+        // Sound travels faster in warmer air, about 3 cents per degree
+        let temperature = pipe.get_sensitivity(PipeInput::AirTemperature).unwrap();
+        assert!(approx(temperature.pitch, 3.02, 0.01));
+        assert!(approx(
+            temperature.derivatives.resonator_length,
+            0.6 / 2.0 / 261.63,
+            0.000001,
+        ));
+
+        // 0.2 mm cutup error
+        let cutup = pipe.get_sensitivity(PipeInput::CutupRatio).unwrap();
+        let cutup_error = 0.0002 / nominal.mouth_width;
+        assert!(approx(
+            cutup.derivatives.mouth_height * cutup_error,
+            0.0002,
+            0.0000001,
+        ));
+
+        // A larger mouth has a smaller end correction
+        assert!(cutup.pitch * cutup_error > 0.0);
+
+        let density = pipe.get_sensitivity(PipeInput::AirDensity).unwrap();
+        assert!(approx(density.pitch, 0.0, 0.000001));
+        assert!(density.derivatives.air_speed < 0.0);
+
+        let tolerances = pipe
+            .get_tolerances(&[(PipeInput::AirTemperature, 2.0)], 2000, 1)
+            .unwrap();
+        assert!(approx(tolerances.pitch_mean, 0.0, 0.5));
+        assert!(approx(tolerances.pitch_standard_deviation, 6.04, 0.3));
+        assert!(approx(
+            tolerances.mean.resonator_length,
+            nominal.resonator_length,
+            0.001,
+        ));
+        assert!(approx(
+            tolerances.standard_deviation.mouth_height,
+            0.0,
+            0.0000001,
+        ));
+
+        let same = pipe
+            .get_tolerances(&[(PipeInput::AirTemperature, 2.0)], 2000, 1)
+            .unwrap();
+        assert_eq!(same.pitch_mean, tolerances.pitch_mean);
+        assert_eq!(same.valid_samples, 2000);
+
+        // A cutup ratio of 0.25 +- 0.2 turns negative in about a tenth of the samples
+        let tolerances = pipe
+            .get_tolerances(&[(PipeInput::CutupRatio, 0.2)], 2000, 1)
+            .unwrap();
+        assert_eq!(tolerances.valid_samples + tolerances.invalid_samples, 2000);
+        assert!(tolerances.invalid_samples > 100 && tolerances.invalid_samples < 300);
+
+        assert_eq!(
+            pipe.get_tolerances(&[(PipeInput::CutupRatio, 0.2)], 0, 1)
+                .unwrap_err(),
+            PipeError::NoSamples
+        );
+
+        // 8' C principal
+        pipe.set_frequency(65.406)
+            .set_radius(Pitch::from(0, 2).get_tnm_radius());
+        let temperature = pipe.get_sensitivity(PipeInput::AirTemperature).unwrap();
+        assert!(approx(temperature.pitch, 3.02, 0.01));
+
+        // The mouth of a wooden pipe spans its width, the ratio can only shrink
+        pipe.set_frequency(130.81)
+            .set_rectangular(0.06, 0.075)
+            .set_mouth_ratio(1.0);
+        let mouth = pipe.get_sensitivity(PipeInput::MouthRatio).unwrap();
+        assert!(approx(mouth.derivatives.mouth_width, 0.06, 0.000001));
+
+        assert_eq!(
+            pipe.get_sensitivity(PipeInput::Radius).unwrap_err(),
+            PipeError::UnsupportedSection
+        );
+        assert_eq!(
+            pipe.get_tolerances(&[(PipeInput::Radius, 0.001)], 100, 1)
+                .unwrap_err(),
+            PipeError::UnsupportedSection
+        );
+    }
+
+    #[test]
+    fn test_temperature_drift() {
+        let mut pipe = c4_pipe();
+        pipe.set_air_temperature(15.0);
+        assert!(approx(pipe.get_temperature_drift(15.0).unwrap(), 0.0, 0.000001));
+        assert!(approx(
+            pipe.get_temperature_drift(25.0).unwrap(),
+            1200.0 * (speed_of_sound(25.0) / speed_of_sound(15.0)).log2(),
+            0.000001,
+        ));
+    }
+
+    #[test]
+    fn test_sound_speed_model() {
+        let mut pipe = c4_pipe();
+        let linear = pipe.get_dimensions();
+
+        pipe.set_sound_speed_model(SoundSpeedModel::new_cramer(0.0));
+        let dry = pipe.get_dimensions();
+        assert!(approx(
+            dry.theoretical_resonator_length,
+            speed_of_sound_cramer(20.0, 0.0, 101_325.0, 0.0004) / 2.0 / 261.63,
+            0.0000001,
+        ));
+        assert!(approx(dry.resonator_length, linear.resonator_length, 0.001));
+
+        // Humid air in a full church raises the pitch of a pipe cut for dry air
+        let resonator_length = dry.resonator_length;
+        pipe.set_sound_speed_model(SoundSpeedModel::new_cramer(0.8));
+        let frequency = pipe.get_sounding_frequency(resonator_length).unwrap();
+        let cents = 1200.0 * (frequency / 261.63).log2();
+        assert!(cents > 3.0 && cents < 6.0);
+    }
 }
@@ -0,0 +1,296 @@
+// This file is part of pipecalc.
+//
+// pipecalc is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// pipecalc is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with pipecalc.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::f64;
+
+use temperament::{Pitch, Temperament};
+use util::{self, SoundSpeedModel};
+
+// Young's modulus in Pa and density in kg/m^3 of tongue brass
+const BRASS_YOUNGS_MODULUS: f64 = 110e9;
+const BRASS_DENSITY: f64 = 8500.0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ReedResonator {
+    // Trompete, Oboe: full length cone, half a wavelength
+    Conical,
+
+    // Krummhorn, Klarinette: closed at the reed, a quarter wavelength
+    Cylindrical,
+
+    // Regal, Vox humana: fraction of the full conical length, e.g. 0.5 or 0.25
+    Fractional(f64),
+}
+
+#[derive(Clone)]
+pub struct ReedPipe {
+    resonator: ReedResonator,
+    frequency: f64,
+
+    // Resonator radius at the shallot and at the top, in m
+    radius: f64,
+    top_radius: f64,
+
+    // in m
+    tongue_thickness: f64,
+
+    // Fraction of the relative pitch change of the resonator the pipe follows
+    resonator_coupling: f64,
+
+    air_temperature: f64,
+    sound_speed_model: SoundSpeedModel,
+    air_density: f64,
+    wind_pressure: f64,
+}
+
+impl Default for ReedPipe {
+    fn default() -> ReedPipe {
+        ReedPipe::new()
+    }
+}
+
+#[derive(Debug)]
+pub struct ReedProperties {
+    // in m
+    pub resonator_length: f64,
+
+    // in m
+    pub theoretical_resonator_length: f64,
+
+    // Free vibrating length of the tongue, in m
+    pub tongue_length: f64,
+
+    // in m
+    pub shallot_opening_length: f64,
+
+    // in m
+    pub shallot_opening_width: f64,
+
+    // in m^2
+    pub shallot_opening_area: f64,
+
+    // in m/s
+    pub air_speed: f64,
+
+    // in m^3/s
+    pub air_consumption_rate: f64,
+}
+
+impl ReedPipe {
+    pub fn new() -> ReedPipe {
+        ReedPipe {
+            resonator: ReedResonator::Conical,
+            frequency: 0.0,
+            radius: 0.0,
+            top_radius: 0.0,
+            tongue_thickness: 0.0004,
+            resonator_coupling: 0.2,
+            air_temperature: 20.0,
+            sound_speed_model: SoundSpeedModel::Linear,
+            air_density: 0.0,
+            wind_pressure: 0.0,
+        }
+    }
+
+    pub fn set_resonator(&mut self, resonator: ReedResonator) -> &mut Self {
+        self.resonator = resonator;
+        self
+    }
+
+    pub fn set_frequency(&mut self, frequency: f64) -> &mut Self {
+        self.frequency = frequency;
+        self
+    }
+
+    pub fn set_pitch(&mut self, pitch: &Pitch, temperament: &Temperament) -> &mut Self {
+        self.set_frequency(pitch.to_frequency(temperament))
+    }
+
+    // Radius at the shallot, also sets the top radius
+    pub fn set_radius(&mut self, radius: f64) -> &mut Self {
+        self.radius = radius;
+        self.top_radius = radius;
+        self
+    }
+
+    // Conical resonators
+    pub fn set_top_radius(&mut self, top_radius: f64) -> &mut Self {
+        self.top_radius = top_radius;
+        self
+    }
+
+    pub fn set_tongue_thickness(&mut self, tongue_thickness: f64) -> &mut Self {
+        self.tongue_thickness = tongue_thickness;
+        self
+    }
+
+    // The tongue mostly sets the pitch, 0 for a free reed, 1 if the resonator alone did
+    pub fn set_resonator_coupling(&mut self, resonator_coupling: f64) -> &mut Self {
+        self.resonator_coupling = resonator_coupling;
+        self
+    }
+
+    pub fn set_air_temperature(&mut self, air_temperature: f64) -> &mut Self {
+        self.air_temperature = air_temperature;
+        self
+    }
+
+    pub fn set_sound_speed_model(&mut self, sound_speed_model: SoundSpeedModel) -> &mut Self {
+        self.sound_speed_model = sound_speed_model;
+        self
+    }
+
+    pub fn set_air_density(&mut self, air_density: f64) -> &mut Self {
+        self.air_density = air_density;
+        self
+    }
+
+    pub fn set_wind_pressure(&mut self, wind_pressure: f64) -> &mut Self {
+        self.wind_pressure = wind_pressure;
+        self
+    }
+
+    // Deviation in cents at another air temperature of this pipe tuned at the set one
+    pub fn get_temperature_drift(&self, air_temperature: f64) -> f64 {
+        let speed_of_sound = self.sound_speed_model.get_speed_of_sound(air_temperature);
+        let nominal = self
+            .sound_speed_model
+            .get_speed_of_sound(self.air_temperature);
+        self.resonator_coupling * 1200.0 * (speed_of_sound / nominal).log2()
+    }
+
+    // air_temperature in C, air_density in kg/m^3, wind_pressure in Pa
+    pub fn get_dimensions(&self) -> ReedProperties {
+        let speed_of_sound = self
+            .sound_speed_model
+            .get_speed_of_sound(self.air_temperature); // in m/s
+        let air_speed = util::air_speed(self.wind_pressure, self.air_density); // in m/s
+        let wavelength = speed_of_sound / self.frequency;
+
+        // The reed end acts as a closed end, a cone completed to its apex resonates like
+        // an open pipe. The truncation of the cone at the shallot is neglected
+        let theoretical_resonator_length = match self.resonator {
+            ReedResonator::Conical => wavelength / 2.0,
+            ReedResonator::Cylindrical => wavelength / 4.0,
+            ReedResonator::Fractional(fraction) => fraction * wavelength / 2.0,
+        };
+
+        // Unflanged open top
+        let resonator_length = theoretical_resonator_length - 0.6 * self.top_radius;
+
+        // Clamped brass cantilever: f = 1.875^2 / (2 pi) * t / L^2 * sqrt(E / (12 rho))
+        let tongue_length = (1.875f64.powi(2) / (2.0 * f64::consts::PI)
+            * self.tongue_thickness
+            * (BRASS_YOUNGS_MODULUS / (12.0 * BRASS_DENSITY)).sqrt()
+            / self.frequency)
+            .sqrt();
+
+        // Estimates, the tongue has to cover the opening: slightly shorter than the
+        // vibrating length and half as wide as the shallot
+        let shallot_opening_length = 0.9 * tongue_length;
+        let shallot_opening_width = self.radius;
+        let shallot_opening_area = shallot_opening_length * shallot_opening_width;
+
+        // A beating tongue closes the opening for about half of each period
+        let air_consumption_rate = 0.5 * air_speed * shallot_opening_area;
+
+        ReedProperties {
+            resonator_length,
+            theoretical_resonator_length,
+            tongue_length,
+            shallot_opening_length,
+            shallot_opening_width,
+            shallot_opening_area,
+            air_speed,
+            air_consumption_rate,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::util::*;
+
+    #[test]
+    fn test_reed_pipe() {
+        let temp = Temperament::new_default_equal();
+        let mut pipe = ReedPipe::new();
+        pipe.set_pitch(&Pitch::from(0, 2), &temp)
+            .set_radius(0.004)
+            .set_top_radius(0.05)
+            .set_air_density(1.2)
+            .set_wind_pressure(mmH2O_to_pa(80.0));
+
+        // Trompete 8' C
+        let trompete = pipe.get_dimensions();
+        let wavelength = speed_of_sound(20.0) / 65.406;
+        assert!(approx(
+            trompete.theoretical_resonator_length,
+            wavelength / 2.0,
+            0.001,
+        ));
+        assert!(approx(trompete.resonator_length, 2.5967, 0.001));
+        assert!(approx(trompete.tongue_length, 0.0596, 0.001));
+        assert!(trompete.air_consumption_rate > 0.0);
+
+        // Krummhorn: half the length of a Trompete
+        pipe.set_resonator(ReedResonator::Cylindrical)
+            .set_radius(0.01);
+        let krummhorn = pipe.get_dimensions();
+        assert!(approx(
+            krummhorn.theoretical_resonator_length,
+            trompete.theoretical_resonator_length / 2.0,
+            0.0000001,
+        ));
+        assert!(approx(
+            krummhorn.resonator_length,
+            krummhorn.theoretical_resonator_length - 0.006,
+            0.0000001,
+        ));
+
+        pipe.set_resonator(ReedResonator::Fractional(0.25));
+        assert!(approx(
+            pipe.get_dimensions().theoretical_resonator_length,
+            wavelength / 8.0,
+            0.001,
+        ));
+
+        // An octave higher shortens the tongue by sqrt(2)
+        pipe.set_pitch(&Pitch::from(0, 3), &temp);
+        assert!(approx(
+            pipe.get_dimensions().tongue_length,
+            trompete.tongue_length / 2f64.sqrt(),
+            0.0001,
+        ));
+    }
+
+    #[test]
+    fn test_reed_temperature_drift() {
+        let mut pipe = ReedPipe::new();
+        pipe.set_frequency(65.406).set_air_temperature(15.0);
+        assert_eq!(pipe.get_temperature_drift(15.0), 0.0);
+
+        let resonator = 1200.0 * (speed_of_sound(25.0) / speed_of_sound(15.0)).log2();
+        assert!(approx(
+            pipe.get_temperature_drift(25.0),
+            0.2 * resonator,
+            0.000001,
+        ));
+
+        pipe.set_resonator_coupling(0.0);
+        assert_eq!(pipe.get_temperature_drift(25.0), 0.0);
+    }
+}
@@ -0,0 +1,635 @@
+// This file is part of pipecalc.
+//
+// pipecalc is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// pipecalc is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with pipecalc.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::error::Error;
+use std::f64;
+use std::fmt;
+use std::fmt::Write;
+
+use midi::SCIENTIFIC_MIDDLE_C;
+use temperament::{Pitch, Temperament};
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ScalaScale {
+    pub description: String,
+
+    // Cents above the tonic, the last degree is the period
+    pub degrees: Vec<f64>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct KeyboardMapping {
+    // MIDI key numbers
+    pub first_key: i32,
+    pub last_key: i32,
+
+    // Key on which scale degree 0 is mapped
+    pub middle_key: i32,
+
+    pub reference_key: i32,
+
+    // in Hz
+    pub reference_frequency: f64,
+
+    // Scale degree between two repetitions of the mapping, 0 = period of the scale
+    pub octave_degree: i32,
+
+    // Scale degree per key starting at middle_key, None = unmapped,
+    // empty = linear mapping
+    pub keys: Vec<Option<i32>>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ScalaFile {
+    Scale,
+    Mapping,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ScalaErrorKind {
+    MissingLine,
+    InvalidCount(String),
+    InvalidPitch(String),
+    InvalidNumber(String),
+    InvalidMapping(String),
+    UnmappedReference,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ScalaError {
+    pub file: ScalaFile,
+
+    // 1-based, points past the last line for a missing line
+    pub line: usize,
+
+    pub kind: ScalaErrorKind,
+}
+
+impl fmt::Display for ScalaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let file = match self.file {
+            ScalaFile::Scale => "scale",
+            ScalaFile::Mapping => "keyboard mapping",
+        };
+        write!(f, "Invalid {} file, line {}: ", file, self.line)?;
+        match self.kind {
+            ScalaErrorKind::MissingLine => write!(f, "unexpected end of file"),
+            ScalaErrorKind::InvalidCount(ref value) => {
+                write!(f, "invalid number of notes \"{}\"", value)
+            }
+            ScalaErrorKind::InvalidPitch(ref value) => write!(f, "invalid pitch \"{}\"", value),
+            ScalaErrorKind::InvalidNumber(ref value) => write!(f, "invalid number \"{}\"", value),
+            ScalaErrorKind::InvalidMapping(ref value) => {
+                write!(f, "invalid mapping entry \"{}\"", value)
+            }
+            ScalaErrorKind::UnmappedReference => write!(f, "reference key is not mapped"),
+        }
+    }
+}
+
+impl Error for ScalaError {}
+
+// Yields (line number, trimmed content) of all lines that are not comments
+struct Lines<'a> {
+    file: ScalaFile,
+    lines: ::std::iter::Enumerate<::std::str::Lines<'a>>,
+    last_line: usize,
+}
+
+impl<'a> Lines<'a> {
+    fn new(file: ScalaFile, text: &'a str) -> Self {
+        Lines {
+            file,
+            lines: text.lines().enumerate(),
+            last_line: 0,
+        }
+    }
+
+    fn next_line(&mut self) -> Result<(usize, &'a str), ScalaError> {
+        for (index, line) in &mut self.lines {
+            self.last_line = index + 1;
+            if !line.starts_with('!') {
+                return Ok((index + 1, line.trim()));
+            }
+        }
+        Err(self.error(self.last_line + 1, ScalaErrorKind::MissingLine))
+    }
+
+    fn next_value(&mut self) -> Result<(usize, &'a str), ScalaError> {
+        let (line, content) = self.next_line()?;
+        Ok((line, content.split_whitespace().next().unwrap_or("")))
+    }
+
+    fn next_number<T: ::std::str::FromStr>(&mut self) -> Result<(usize, T), ScalaError> {
+        let (line, value) = self.next_value()?;
+        match value.parse() {
+            Ok(number) => Ok((line, number)),
+            Err(_) => Err(self.error(line, ScalaErrorKind::InvalidNumber(value.to_string()))),
+        }
+    }
+
+    fn error(&self, line: usize, kind: ScalaErrorKind) -> ScalaError {
+        ScalaError {
+            file: self.file,
+            line,
+            kind,
+        }
+    }
+}
+
+fn parse_pitch(value: &str) -> Option<f64> {
+    if value.contains('.') {
+        return value.parse().ok();
+    }
+
+    let mut parts = value.splitn(2, '/');
+    let numerator: u64 = parts.next()?.parse().ok()?;
+    let denominator: u64 = match parts.next() {
+        Some(denominator) => denominator.parse().ok()?,
+        None => 1,
+    };
+    if numerator == 0 || denominator == 0 {
+        return None;
+    }
+    Some(1200.0 * (numerator as f64 / denominator as f64).log2())
+}
+
+impl ScalaScale {
+    pub fn parse(text: &str) -> Result<ScalaScale, ScalaError> {
+        let mut lines = Lines::new(ScalaFile::Scale, text);
+
+        let (_, description) = lines.next_line()?;
+        let (line, count) = lines.next_value()?;
+        let count: usize = match count.parse() {
+            Ok(count) if count > 0 => count,
+            _ => {
+                return Err(lines.error(line, ScalaErrorKind::InvalidCount(count.to_string())));
+            }
+        };
+
+        // Not preallocated, the count comes from the file
+        let mut degrees = Vec::new();
+        for _ in 0..count {
+            let (line, value) = lines.next_value()?;
+            match parse_pitch(value) {
+                Some(cents) => degrees.push(cents),
+                None => {
+                    return Err(lines.error(line, ScalaErrorKind::InvalidPitch(value.to_string())));
+                }
+            }
+        }
+
+        Ok(ScalaScale {
+            description: description.to_string(),
+            degrees,
+        })
+    }
+
+    pub fn to_scl(&self) -> String {
+        let mut scl = String::new();
+        writeln!(scl, "!").unwrap();
+        writeln!(scl, "{}", self.description).unwrap();
+        writeln!(scl, " {}", self.degrees.len()).unwrap();
+        writeln!(scl, "!").unwrap();
+        for cents in &self.degrees {
+            writeln!(scl, " {:.6}", cents).unwrap();
+        }
+        scl
+    }
+
+    pub fn get_period(&self) -> f64 {
+        *self.degrees.last().unwrap_or(&0.0)
+    }
+
+    // Cents of any scale degree above the tonic, continuing over periods
+    pub fn get_cents(&self, degree: i32) -> f64 {
+        let size = self.degrees.len() as i32;
+        if size == 0 {
+            return 0.0;
+        }
+        let period = f64::from(degree.div_euclid(size));
+        let index = degree.rem_euclid(size);
+        let cents = if index == 0 {
+            0.0
+        } else {
+            self.degrees[index as usize - 1]
+        };
+        period * self.get_period() + cents
+    }
+}
+
+// A longer mapping pattern would never repeat within the MIDI keys
+const MAX_MAP_SIZE: usize = 128;
+
+impl KeyboardMapping {
+    pub fn new_linear(middle_key: i32, reference_key: i32, reference_frequency: f64) -> Self {
+        KeyboardMapping {
+            first_key: 0,
+            last_key: 127,
+            middle_key,
+            reference_key,
+            reference_frequency,
+            octave_degree: 0,
+            keys: Vec::new(),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<KeyboardMapping, ScalaError> {
+        let mut lines = Lines::new(ScalaFile::Mapping, text);
+
+        let (size_line, size) = lines.next_number::<usize>()?;
+        if size > MAX_MAP_SIZE {
+            return Err(lines.error(size_line, ScalaErrorKind::InvalidNumber(size.to_string())));
+        }
+        let (_, first_key) = lines.next_number()?;
+        let (_, last_key) = lines.next_number()?;
+        let (_, middle_key) = lines.next_number()?;
+        let (reference_line, reference_key) = lines.next_number()?;
+        let (_, reference_frequency) = lines.next_number()?;
+        let (_, octave_degree) = lines.next_number()?;
+
+        let mut keys = Vec::new();
+        for _ in 0..size {
+            // Missing entries at the end are unmapped
+            let (line, value) = match lines.next_value() {
+                Ok(entry) => entry,
+                Err(_) => break,
+            };
+            if value == "x" || value == "X" {
+                keys.push(None);
+                continue;
+            }
+            match value.parse() {
+                Ok(degree) => keys.push(Some(degree)),
+                Err(_) => {
+                    return Err(
+                        lines.error(line, ScalaErrorKind::InvalidMapping(value.to_string()))
+                    );
+                }
+            }
+        }
+        keys.resize(size, None);
+
+        let mapping = KeyboardMapping {
+            first_key,
+            last_key,
+            middle_key,
+            reference_key,
+            reference_frequency,
+            octave_degree,
+            keys,
+        };
+        if !mapping.is_mapped(reference_key) {
+            return Err(lines.error(reference_line, ScalaErrorKind::UnmappedReference));
+        }
+        Ok(mapping)
+    }
+
+    pub fn to_kbm(&self) -> String {
+        let mut kbm = String::new();
+        writeln!(kbm, "! Map size").unwrap();
+        writeln!(kbm, "{}", self.keys.len()).unwrap();
+        writeln!(kbm, "! First and last MIDI key to retune").unwrap();
+        writeln!(kbm, "{}", self.first_key).unwrap();
+        writeln!(kbm, "{}", self.last_key).unwrap();
+        writeln!(kbm, "! Middle key on which scale degree 0 is mapped").unwrap();
+        writeln!(kbm, "{}", self.middle_key).unwrap();
+        writeln!(kbm, "! Reference key and its frequency").unwrap();
+        writeln!(kbm, "{}", self.reference_key).unwrap();
+        writeln!(kbm, "{:.6}", self.reference_frequency).unwrap();
+        writeln!(kbm, "! Scale degree of the formal octave").unwrap();
+        writeln!(kbm, "{}", self.octave_degree).unwrap();
+        writeln!(kbm, "! Mapping").unwrap();
+        for key in &self.keys {
+            match key {
+                Some(degree) => writeln!(kbm, "{}", degree).unwrap(),
+                None => writeln!(kbm, "x").unwrap(),
+            }
+        }
+        kbm
+    }
+
+    pub fn is_mapped(&self, key: i32) -> bool {
+        if key < self.first_key || key > self.last_key {
+            return false;
+        }
+        self.keys.is_empty()
+            || self.keys[(key - self.middle_key).rem_euclid(self.keys.len() as i32) as usize]
+                .is_some()
+    }
+
+    // (degree, repetitions of the mapping pattern)
+    pub fn get_degree(&self, key: i32) -> Option<(i32, i32)> {
+        if !self.is_mapped(key) {
+            return None;
+        }
+
+        let distance = key - self.middle_key;
+        if self.keys.is_empty() {
+            return Some((distance, 0));
+        }
+
+        let size = self.keys.len() as i32;
+        self.keys[distance.rem_euclid(size) as usize]
+            .map(|degree| (degree, distance.div_euclid(size)))
+    }
+
+    // Cents above the middle key, each repetition of the mapping pattern adds the formal
+    // octave degree of the scale
+    pub fn get_cents(&self, key: i32, scale: &ScalaScale) -> Option<f64> {
+        let octave_degree = if self.octave_degree == 0 {
+            scale.degrees.len() as i32
+        } else {
+            self.octave_degree
+        };
+        self.get_degree(key).map(|(degree, repetitions)| {
+            scale.get_cents(degree) + f64::from(repetitions) * scale.get_cents(octave_degree)
+        })
+    }
+
+    // NaN for unmapped keys
+    pub fn get_frequency(&self, key: i32, scale: &ScalaScale) -> f64 {
+        match (
+            self.get_cents(key, scale),
+            self.get_cents(self.reference_key, scale),
+        ) {
+            (Some(cents), Some(reference)) => {
+                self.reference_frequency * (2.0f64).powf((cents - reference) / 1200.0)
+            }
+            _ => f64::NAN,
+        }
+    }
+}
+
+impl Temperament {
+    // Without a keyboard mapping, scale degree 0 lies on C4 and A4 is 440 Hz
+    pub fn from_scala(scl: &str, kbm: Option<&str>) -> Result<Temperament, ScalaError> {
+        let scale = ScalaScale::parse(scl)?;
+        let mapping = match kbm {
+            Some(kbm) => KeyboardMapping::parse(kbm)?,
+            None => KeyboardMapping::new_linear(60, 69, 440.0),
+        };
+        Ok(Temperament::SCALA { scale, mapping })
+    }
+
+    pub fn to_scala(&self) -> (ScalaScale, KeyboardMapping) {
+        let description = match self {
+            Temperament::EQUAL { .. } => "12-tone equal temperament",
+            Temperament::WERCKMEISTER3 { .. } => "Werckmeister III",
+            Temperament::KIRNBERGER3 { .. } => "Kirnberger III",
+            Temperament::VALLOTTI { .. } => "Vallotti",
+            Temperament::YOUNG { .. } => "Young",
+            Temperament::MEANTONE { .. } => "1/4-comma meantone",
+            Temperament::JUST { .. } => "Just intonation",
+            Temperament::SCALA { scale, mapping } => return (scale.clone(), mapping.clone()),
+            Temperament::EDO {
+                divisions,
+                period,
+                base_frequency,
+                base_octave,
+            } => {
+                let period = 1200.0 * period.log2();
+                let scale = ScalaScale {
+                    description: format!("{} equal divisions of {:.6} cents", divisions, period),
+                    degrees: (1..=*divisions)
+                        .map(|step| period * f64::from(step) / f64::from(*divisions))
+                        .collect(),
+                };
+                let key = Pitch::from(0, *base_octave).to_midi(SCIENTIFIC_MIDDLE_C);
+                let mut mapping = KeyboardMapping::new_linear(key, key, *base_frequency);
+                mapping.octave_degree = *divisions as i32;
+                return (scale, mapping);
+            }
+        };
+
+        // Degree 0 is C, the reference is the key of the base note
+        let c4 = Pitch::from(0, 4).to_frequency(self);
+        let degrees = (1..13)
+            .map(|note| {
+                let frequency = Pitch::from(note, 4).to_frequency(self);
+                1200.0 * (frequency / c4).log2()
+            })
+            .collect();
+        let base_note = self.get_base_note();
+        let reference_key = Pitch::from(base_note.get_note(), base_note.get_octave());
+
+        let scale = ScalaScale {
+            description: description.to_string(),
+            degrees,
+        };
+        let mapping = KeyboardMapping {
+            first_key: 0,
+            last_key: 127,
+            middle_key: 60,
+            reference_key: reference_key.to_midi(SCIENTIFIC_MIDDLE_C),
+            reference_frequency: reference_key.to_frequency(self),
+            octave_degree: 12,
+            keys: (0..12).map(Some).collect(),
+        };
+        (scale, mapping)
+    }
+
+    pub fn to_scl(&self) -> String {
+        self.to_scala().0.to_scl()
+    }
+
+    pub fn to_kbm(&self) -> String {
+        self.to_scala().1.to_kbm()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::*;
+    use super::super::util::*;
+
+    const MEANTONE_SCL: &str = "! meanquar.scl
+!
+1/4-comma meantone scale. Pietro Aaron's temperament (1523)
+ 12
+!
+ 76.04900
+ 193.15686
+ 310.26471
+ 5/4
+ 503.42157
+ 579.47057
+ 696.57843
+ 25/16
+ 889.73529
+ 1006.84314
+ 1082.89214
+ 2/1
+";
+
+    const MAPPING_KBM: &str = "! A4 = 415 Hz, white keys only
+12
+0
+127
+60
+69
+415.0
+7
+0
+x
+1
+x
+2
+3
+x
+4
+x
+5
+x
+";
+
+    #[test]
+    fn test_scala_import() {
+        let temp = Temperament::from_scala(MEANTONE_SCL, None).unwrap();
+        let meantone = Temperament::new_default_meantone();
+        for note in 0..24 {
+            let pitch = Pitch::from(note, 3);
+            assert!(approx(
+                pitch.to_frequency(&temp),
+                pitch.to_frequency(&meantone),
+                0.001,
+            ));
+        }
+
+        let scl = "12-EDO\n12\n100.\n200.\n300.\n400.\n500.\n600.\n700.\n800.\n900.\n1000.\n\
+                   1100.\n2/1\n";
+        let kbm = "0\n0\n127\n60\n69\n415.0\n12\n";
+        let temp = Temperament::from_scala(scl, Some(kbm)).unwrap();
+        let equal = Temperament::new_freq_equal(415.0);
+        assert!(approx(
+            Pitch::from(3, 2).to_frequency(&temp),
+            Pitch::from(3, 2).to_frequency(&equal),
+            0.001,
+        ));
+    }
+
+    #[test]
+    fn test_keyboard_mapping() {
+        let scale = ScalaScale::parse(MEANTONE_SCL).unwrap();
+        let mapping = KeyboardMapping::parse(MAPPING_KBM).unwrap();
+        assert_eq!(mapping.keys.len(), 12);
+        assert_eq!(mapping.keys[11], None);
+        assert_eq!(mapping.get_degree(60), Some((0, 0)));
+        assert_eq!(mapping.get_degree(61), None);
+        assert_eq!(mapping.get_degree(71), None);
+        assert_eq!(mapping.get_degree(72), Some((0, 1)));
+        assert_eq!(mapping.get_degree(57), Some((5, -1)));
+        assert!(approx(mapping.get_frequency(69, &scale), 415.0, 0.0001));
+
+        // The formal octave adds the cents of its degree, not its index
+        let scale = ScalaScale::parse(
+            "5-limit\n12\n16/15\n9/8\n6/5\n5/4\n4/3\n45/32\n3/2\n8/5\n5/3\n9/5\n15/8\n2/1\n",
+        )
+        .unwrap();
+        let mapping = KeyboardMapping::parse("2\n0\n127\n60\n60\n261.0\n7\n0\n6\n").unwrap();
+        assert_eq!(mapping.get_degree(63), Some((6, 1)));
+        assert!(approx(
+            mapping.get_cents(63, &scale).unwrap(),
+            1200.0 * (45.0f64 / 32.0 * 3.0 / 2.0).log2(),
+            0.0001,
+        ));
+        assert!(approx(mapping.get_frequency(63, &scale), 550.55, 0.01));
+    }
+
+    #[test]
+    fn test_scala_export() {
+        let temperaments = vec![
+            Temperament::new_freq_equal(442.0),
+            Temperament::new_default_werckmeister3(),
+            Temperament::new_default_kirnberger3(),
+            Temperament::new_default_vallotti(),
+            Temperament::new_default_young(),
+            Temperament::new_freq_meantone(415.0),
+            Temperament::new_default_just(Pitch::from(7, 0)),
+            Temperament::from_scala(MEANTONE_SCL, Some(MAPPING_KBM)).unwrap(),
+        ];
+
+        for temp in temperaments {
+            let imported = Temperament::from_scala(&temp.to_scl(), Some(&temp.to_kbm())).unwrap();
+            for note in 0..48 {
+                let pitch = Pitch::from(note, 2);
+                let expected = pitch.to_frequency(&temp);
+                let actual = pitch.to_frequency(&imported);
+                assert!(expected.is_nan() && actual.is_nan() || approx(expected, actual, 0.0001));
+            }
+        }
+
+        // EDO steps lie on consecutive keys from C4
+        for temp in &[
+            Temperament::new_edo(19, 261.0).unwrap(),
+            Temperament::new_period_edo(13, 3.0, 100.0).unwrap(),
+        ] {
+            let imported = Temperament::from_scala(&temp.to_scl(), Some(&temp.to_kbm())).unwrap();
+            for step in -24..24 {
+                assert!(approx(
+                    Pitch::from_midi(60 + step, SCIENTIFIC_MIDDLE_C).to_frequency(&imported),
+                    temp.get_step_frequency(step, 4),
+                    0.0001,
+                ));
+            }
+        }
+
+        let (scale, mapping) = Temperament::new_default_werckmeister3().to_scala();
+        assert_eq!(scale.degrees.len(), 12);
+        assert!(approx(scale.degrees[0], 90.225, 0.0001));
+        assert!(approx(scale.get_period(), 1200.0, 0.0001));
+        assert_eq!(mapping.reference_key, 69);
+        assert!(approx(mapping.reference_frequency, 440.0, 0.0001));
+    }
+
+    #[test]
+    fn test_scala_errors() {
+        let error = ScalaScale::parse("Broken\n3\n100.0\n3/x\n").unwrap_err();
+        assert_eq!(error.line, 4);
+        assert_eq!(error.kind, ScalaErrorKind::InvalidPitch("3/x".to_string()));
+
+        let error = ScalaScale::parse("! only a comment\nShort\n3\n100.0\n").unwrap_err();
+        assert_eq!(error.line, 5);
+        assert_eq!(error.kind, ScalaErrorKind::MissingLine);
+
+        let error = ScalaScale::parse("Empty\nmany\n").unwrap_err();
+        assert_eq!(error.kind, ScalaErrorKind::InvalidCount("many".to_string()));
+
+        let error = KeyboardMapping::parse("12\n0\n127\n60\n69\nfast\n").unwrap_err();
+        assert_eq!(error.file, ScalaFile::Mapping);
+        assert_eq!(error.line, 6);
+        assert_eq!(
+            error.kind,
+            ScalaErrorKind::InvalidNumber("fast".to_string())
+        );
+
+        let error = KeyboardMapping::parse("2\n0\n127\n60\n61\n440.0\n1\n0\nx\n").unwrap_err();
+        assert_eq!(error.line, 5);
+        assert_eq!(error.kind, ScalaErrorKind::UnmappedReference);
+
+        let error = ScalaScale::parse("x\n99999999999999\n100.0\n").unwrap_err();
+        assert_eq!(error.line, 4);
+        assert_eq!(error.kind, ScalaErrorKind::MissingLine);
+
+        let error =
+            KeyboardMapping::parse("99999999999999\n0\n127\n60\n69\n440.0\n0\n").unwrap_err();
+        assert_eq!(error.line, 1);
+        assert_eq!(
+            error.kind,
+            ScalaErrorKind::InvalidNumber("99999999999999".to_string())
+        );
+    }
+}
@@ -0,0 +1,423 @@
+// This file is part of pipecalc.
+//
+// pipecalc is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// pipecalc is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with pipecalc.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::f64;
+
+use compass::PitchRange;
+use temperament::Pitch;
+
+// Pipe scale (Mensur): diameters shrink by a constant ratio per semitone
+#[derive(Clone, PartialEq, Debug)]
+pub struct Scale {
+    // in m
+    reference_diameter: f64,
+
+    reference_pitch: Pitch,
+
+    // Diameter ratio between neighbouring semitones
+    ratio: f64,
+
+    // Semitones, positive values widen the scale
+    offset: f64,
+}
+
+// Result of fitting a scale through measured pipes
+#[derive(Clone, PartialEq, Debug)]
+pub struct ScaleFit {
+    pub scale: Scale,
+
+    // Semitones against the normal scale at the mean measured pitch, like Scale::set_offset
+    pub normal_offset: f64,
+
+    // Measured minus fitted diameter per sample, in m
+    pub residuals: Vec<f64>,
+}
+
+impl Scale {
+    // Töpfer normal scale: 155.55 mm at C2, halving on the 17th
+    pub fn new_normal() -> Scale {
+        Scale::from_ratio(0.15555, Pitch::from(0, 2), 0.957458)
+    }
+
+    // reference_diameter in m
+    pub fn new(reference_diameter: f64, reference_pitch: Pitch, halving_semitones: f64) -> Scale {
+        Scale::from_ratio(
+            reference_diameter,
+            reference_pitch,
+            0.5f64.powf(1.0 / halving_semitones),
+        )
+    }
+
+    // Halving interval counted like musical intervals, e.g. 17 for the 17th (16 semitones)
+    pub fn new_halving_on(reference_diameter: f64, reference_pitch: Pitch, interval: u32) -> Scale {
+        Scale::new(
+            reference_diameter,
+            reference_pitch,
+            f64::from(interval) - 1.0,
+        )
+    }
+
+    pub fn from_ratio(reference_diameter: f64, reference_pitch: Pitch, ratio: f64) -> Scale {
+        Scale {
+            reference_diameter,
+            reference_pitch,
+            ratio,
+            offset: 0.0,
+        }
+    }
+
+    // Semitones against the reference, e.g. +2 gives every pipe the diameter of the pipe
+    // two semitones lower
+    pub fn set_offset(&mut self, offset: f64) -> &mut Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn get_offset(&self) -> f64 {
+        self.offset
+    }
+
+    pub fn get_ratio(&self) -> f64 {
+        self.ratio
+    }
+
+    pub fn get_reference_diameter(&self) -> f64 {
+        self.reference_diameter
+    }
+
+    pub fn get_reference_pitch(&self) -> &Pitch {
+        &self.reference_pitch
+    }
+
+    // in semitones
+    pub fn get_halving_interval(&self) -> f64 {
+        0.5f64.ln() / self.ratio.ln()
+    }
+
+    // Least squares fit of the logarithmic diameters of (pitch, diameter in m) samples.
+    // None for fewer than two distinct pitches or non-positive diameters
+    pub fn fit(samples: &[(Pitch, f64)]) -> Option<ScaleFit> {
+        if samples.iter().any(|&(_, diameter)| diameter <= 0.0) {
+            return None;
+        }
+
+        let reference_pitch = Pitch::from(0, 2);
+        let count = samples.len() as f64;
+        let xs: Vec<f64> = samples
+            .iter()
+            .map(|(pitch, _)| pitch.difference_to(&reference_pitch))
+            .collect();
+        let ys: Vec<f64> = samples.iter().map(|&(_, diameter)| diameter.ln()).collect();
+
+        let mean_x = xs.iter().sum::<f64>() / count;
+        let mean_y = ys.iter().sum::<f64>() / count;
+        let variance: f64 = xs.iter().map(|x| (x - mean_x).powi(2)).sum();
+        if samples.len() < 2 || variance < 1e-9 {
+            return None;
+        }
+        let covariance: f64 = xs
+            .iter()
+            .zip(ys.iter())
+            .map(|(x, y)| (x - mean_x) * (y - mean_y))
+            .sum();
+
+        let slope = covariance / variance;
+        let intercept = mean_y - slope * mean_x;
+        let scale = Scale::from_ratio(intercept.exp(), reference_pitch, slope.exp());
+
+        let normal = Scale::new_normal();
+        let mean_pitch = Pitch::from(0, 2).add(mean_x * 100.0);
+        let normal_offset = (scale.get_diameter(&mean_pitch) / normal.get_diameter(&mean_pitch))
+            .ln()
+            / -normal.get_ratio().ln();
+
+        let residuals = samples
+            .iter()
+            .map(|(pitch, diameter)| diameter - scale.get_diameter(pitch))
+            .collect();
+
+        Some(ScaleFit {
+            scale,
+            normal_offset,
+            residuals,
+        })
+    }
+
+    // in m
+    pub fn get_diameter(&self, pitch: &Pitch) -> f64 {
+        let steps = pitch.difference_to(&self.reference_pitch) - self.offset;
+        self.reference_diameter * self.ratio.powf(steps)
+    }
+
+    // in m
+    pub fn get_radius(&self, pitch: &Pitch) -> f64 {
+        self.get_diameter(pitch) / 2.0
+    }
+}
+
+// Anything that assigns a diameter to every pitch of a rank
+pub trait PipeScale {
+    // in m
+    fn get_diameter(&self, pitch: &Pitch) -> f64;
+
+    // in m
+    fn get_radius(&self, pitch: &Pitch) -> f64 {
+        self.get_diameter(pitch) / 2.0
+    }
+}
+
+impl PipeScale for Scale {
+    fn get_diameter(&self, pitch: &Pitch) -> f64 {
+        Scale::get_diameter(self, pitch)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ScaleSegment {
+    pub range: PitchRange,
+    pub scale: Scale,
+}
+
+// Jump between two segments at the last pitch of the lower one
+#[derive(Clone, PartialEq, Debug)]
+pub struct Discontinuity {
+    pub pitch: Pitch,
+
+    // in m
+    pub diameter_before: f64,
+    pub diameter_after: f64,
+}
+
+// Scale made of segments with their own halving interval, e.g. a wider bass and a
+// narrower treble
+#[derive(Clone, PartialEq, Debug)]
+pub struct PiecewiseScale {
+    segments: Vec<ScaleSegment>,
+}
+
+impl PiecewiseScale {
+    pub fn new() -> Self {
+        PiecewiseScale {
+            segments: Vec::new(),
+        }
+    }
+
+    // Segments are expected in ascending order, jumps are reported by get_discontinuities
+    pub fn add_segment(&mut self, range: PitchRange, scale: Scale) -> &mut Self {
+        self.segments.push(ScaleSegment { range, scale });
+        self
+    }
+
+    // Continues the last segment up to end without a jump in diameter.
+    // Panics if there is no segment yet
+    pub fn add_continuous_segment(&mut self, end: Pitch, halving_semitones: f64) -> &mut Self {
+        let (start, scale) = {
+            let last = self
+                .segments
+                .last()
+                .expect("PiecewiseScale needs a first segment to continue");
+            let boundary = last.range.get_end().clone();
+            let diameter = last.scale.get_diameter(&boundary);
+            (
+                boundary.add(100.0),
+                Scale::new(diameter, boundary, halving_semitones),
+            )
+        };
+        self.add_segment(PitchRange::new(start, end), scale)
+    }
+
+    pub fn get_segments(&self) -> &[ScaleSegment] {
+        &self.segments
+    }
+
+    // Compares both neighbouring scales at the last pitch of the lower segment
+    pub fn get_discontinuities(&self, tolerance: f64) -> Vec<Discontinuity> {
+        self.segments
+            .windows(2)
+            .filter_map(|pair| {
+                let pitch = pair[0].range.get_end().clone();
+                let diameter_before = pair[0].scale.get_diameter(&pitch);
+                let diameter_after = pair[1].scale.get_diameter(&pitch);
+                if (diameter_before - diameter_after).abs() > tolerance {
+                    Some(Discontinuity {
+                        pitch,
+                        diameter_before,
+                        diameter_after,
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    // Pitches outside of all segments use the nearest lower segment, or the first one
+    fn get_segment(&self, pitch: &Pitch) -> Option<&ScaleSegment> {
+        self.segments
+            .iter()
+            .find(|segment| segment.range.contains(pitch))
+            .or_else(|| {
+                self.segments
+                    .iter()
+                    .rev()
+                    .find(|segment| pitch.difference_to(segment.range.get_start()) >= 0.0)
+            })
+            .or_else(|| self.segments.first())
+    }
+}
+
+impl Default for PiecewiseScale {
+    fn default() -> Self {
+        PiecewiseScale::new()
+    }
+}
+
+impl PipeScale for PiecewiseScale {
+    // NaN without segments
+    fn get_diameter(&self, pitch: &Pitch) -> f64 {
+        match self.get_segment(pitch) {
+            Some(segment) => segment.scale.get_diameter(pitch),
+            None => f64::NAN,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::pipe::Pipe;
+    use super::super::util::*;
+
+    #[test]
+    fn test_scales() {
+        let normal = Scale::new_normal();
+        assert!(approx(normal.get_halving_interval(), 16.0, 0.1));
+        for note in 0..60 {
+            let pitch = Pitch::from(note, 1);
+            assert!(approx(
+                normal.get_radius(&pitch),
+                pitch.get_tnm_radius(),
+                0.000001,
+            ));
+        }
+
+        let scale = Scale::new_halving_on(0.1, Pitch::from(0, 2), 19);
+        assert!(approx(scale.get_halving_interval(), 18.0, 0.0001));
+        assert!(approx(
+            scale.get_diameter(&Pitch::from(6, 3)),
+            0.05,
+            0.000001,
+        ));
+        assert!(approx(
+            scale.get_diameter(&Pitch::from(6, 0)),
+            0.2,
+            0.000001,
+        ));
+
+        let mut scale = Scale::new(0.1, Pitch::from(0, 2), 16.0);
+        scale.set_offset(16.0);
+        assert!(approx(
+            scale.get_diameter(&Pitch::from(0, 2)),
+            0.2,
+            0.000001,
+        ));
+        assert!(approx(
+            scale.get_diameter(&Pitch::from(4, 3)),
+            0.1,
+            0.000001,
+        ));
+    }
+
+    #[test]
+    fn test_scale_fit() {
+        let mut wide = Scale::new_normal();
+        wide.set_offset(2.0);
+        let samples: Vec<(Pitch, f64)> = [(0, 2), (7, 2), (0, 3), (9, 4)]
+            .iter()
+            .map(|&(note, octave)| {
+                let pitch = Pitch::from(note, octave);
+                let diameter = wide.get_diameter(&pitch);
+                (pitch, diameter)
+            })
+            .collect();
+        let fit = Scale::fit(&samples).unwrap();
+        assert!(approx(fit.scale.get_ratio(), 0.957458, 0.000001));
+        assert!(approx(fit.normal_offset, 2.0, 0.0001));
+        assert!(fit.residuals.iter().all(|residual| residual.abs() < 1e-9));
+
+        // One pipe 1 mm too wide
+        let mut measured = samples.clone();
+        measured[1].1 += 0.001;
+        let fit = Scale::fit(&measured).unwrap();
+        assert!(fit.residuals[1] > 0.0);
+        assert!(fit.residuals[1] > fit.residuals[0].abs());
+
+        assert!(Scale::fit(&samples[..1]).is_none());
+        assert!(Scale::fit(&[(Pitch::from(0, 2), 0.1), (Pitch::from(0, 2), 0.11)]).is_none());
+        assert!(Scale::fit(&[(Pitch::from(0, 2), 0.1), (Pitch::from(0, 3), 0.0)]).is_none());
+    }
+
+    #[test]
+    fn test_piecewise_scale() {
+        let mut scale = PiecewiseScale::new();
+        scale
+            .add_segment(
+                PitchRange::new(Pitch::from(0, 2), Pitch::from(11, 2)),
+                Scale::new_halving_on(0.16, Pitch::from(0, 2), 16),
+            )
+            .add_continuous_segment(Pitch::from(11, 4), 17.0)
+            .add_continuous_segment(Pitch::from(7, 6), 18.0);
+        assert_eq!(scale.get_segments().len(), 3);
+        assert!(scale.get_discontinuities(1e-9).is_empty());
+
+        assert!(approx(scale.get_diameter(&Pitch::from(0, 2)), 0.16, 1e-9));
+        let b2 = scale.get_diameter(&Pitch::from(11, 2));
+        assert!(approx(b2, 0.16 * 0.5f64.powf(11.0 / 15.0), 1e-9));
+        assert!(approx(
+            scale.get_diameter(&Pitch::from(0, 3)),
+            b2 * 0.5f64.powf(1.0 / 17.0),
+            1e-9,
+        ));
+        assert!(approx(
+            scale.get_radius(&Pitch::from(11, 4)),
+            b2 * 0.5f64.powf(24.0 / 17.0) / 2.0,
+            1e-9,
+        ));
+        // Below and above the compass
+        assert!(approx(
+            scale.get_diameter(&Pitch::from(11, 1)),
+            0.16 * 0.5f64.powf(-1.0 / 15.0),
+            1e-9,
+        ));
+        assert!(scale.get_diameter(&Pitch::from(0, 7)) < scale.get_diameter(&Pitch::from(7, 6)));
+
+        let mut pipe = Pipe::new();
+        pipe.set_scaled_radius(&scale, &Pitch::from(0, 2));
+        assert!(approx(
+            pipe.get_dimensions().cross_section,
+            f64::consts::PI * 0.08f64.powi(2),
+            1e-9,
+        ));
+
+        scale.add_segment(
+            PitchRange::new(Pitch::from(8, 6), Pitch::from(0, 7)),
+            Scale::new_normal(),
+        );
+        let discontinuities = scale.get_discontinuities(0.0001);
+        assert_eq!(discontinuities.len(), 1);
+        assert_eq!(discontinuities[0].pitch, Pitch::from(7, 6));
+        assert!(discontinuities[0].diameter_after < discontinuities[0].diameter_before);
+    }
+}
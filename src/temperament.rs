@@ -15,11 +15,115 @@
 
 use std::f64;
 
+use midi::SCIENTIFIC_MIDDLE_C;
+use scala::{KeyboardMapping, ScalaScale};
+use scale::Scale;
+
+// Cents above C for C, C#, D, ..., B
+const WERCKMEISTER3_CENTS: [f64; 12] = [
+    0.0, 90.225, 192.180, 294.135, 390.225, 498.045, 588.270, 696.090, 792.180, 888.270, 996.090,
+    1092.180,
+];
+
+const KIRNBERGER3_CENTS: [f64; 12] = [
+    0.0, 90.225, 193.157, 294.135, 386.314, 498.045, 590.224, 696.578, 792.180, 889.735, 996.090,
+    1088.269,
+];
+
+const VALLOTTI_CENTS: [f64; 12] = [
+    0.0, 94.135, 196.090, 298.045, 392.180, 501.955, 592.180, 698.045, 796.090, 894.135, 1000.000,
+    1090.225,
+];
+
+const YOUNG_CENTS: [f64; 12] = [
+    0.0, 90.225, 196.090, 294.135, 392.180, 498.045, 588.270, 698.045, 792.180, 894.135, 996.090,
+    1090.225,
+];
+
+// Quarter-comma meantone with the wolf between G# and Eb
+const MEANTONE_CENTS: [f64; 12] = [
+    0.0, 76.049, 193.157, 310.265, 386.314, 503.422, 579.471, 696.578, 772.627, 889.735, 1006.843,
+    1082.892,
+];
+
+// Ratios above the tonic for all 12 semitones
+pub const JUST_5_LIMIT: [(u32, u32); 12] = [
+    (1, 1),
+    (16, 15),
+    (9, 8),
+    (6, 5),
+    (5, 4),
+    (4, 3),
+    (45, 32),
+    (3, 2),
+    (8, 5),
+    (5, 3),
+    (9, 5),
+    (15, 8),
+];
+
+pub const JUST_7_LIMIT: [(u32, u32); 12] = [
+    (1, 1),
+    (16, 15),
+    (8, 7),
+    (6, 5),
+    (5, 4),
+    (4, 3),
+    (7, 5),
+    (3, 2),
+    (8, 5),
+    (5, 3),
+    (7, 4),
+    (15, 8),
+];
+
 pub enum Temperament {
     EQUAL {
         base_frequency: f64,
         base_pitch: Pitch,
     },
+    WERCKMEISTER3 {
+        base_frequency: f64,
+        base_pitch: Pitch,
+    },
+    KIRNBERGER3 {
+        base_frequency: f64,
+        base_pitch: Pitch,
+    },
+    VALLOTTI {
+        base_frequency: f64,
+        base_pitch: Pitch,
+    },
+    YOUNG {
+        base_frequency: f64,
+        base_pitch: Pitch,
+    },
+    // Quarter-comma meantone
+    MEANTONE {
+        base_frequency: f64,
+        base_pitch: Pitch,
+    },
+    // Pure intervals above the note of tonic, the octave of tonic is ignored
+    JUST {
+        base_frequency: f64,
+        base_pitch: Pitch,
+        tonic: Pitch,
+        ratios: [(u32, u32); 12],
+    },
+    // Equal division of the period (2.0 = octave). base_frequency is the frequency of step 0
+    // in base_octave, which lies on C of that octave. Pitches keep their 12-tone meaning,
+    // steps are addressed with get_step_frequency, get_step_pitch and step_for_frequency
+    EDO {
+        divisions: u32,
+        period: f64,
+        base_frequency: f64,
+        base_octave: i32,
+    },
+    // Imported from Scala files, A4 is the reference key of the mapping
+    SCALA {
+        scale: ScalaScale,
+        mapping: KeyboardMapping,
+    },
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -55,6 +159,18 @@ impl Pitch {
         pitch
     }
 
+    pub fn get_note(&self) -> i32 {
+        self.note
+    }
+
+    pub fn get_octave(&self) -> i32 {
+        self.octave
+    }
+
+    pub fn get_cents(&self) -> f64 {
+        self.cents
+    }
+
     pub fn add(&self, cents: f64) -> Self {
         let mut pitch = Pitch {
             note: self.note,
@@ -98,17 +214,11 @@ impl Pitch {
     pub fn to_frequency(&self, temperament: &Temperament) -> f64 {
         let other_note = temperament.get_base_note();
         let steps = self.difference_to(&other_note);
-        temperament.get_frequency(steps as f64)
+        temperament.get_frequency(steps)
     }
 
     pub fn get_tnm_radius(&self) -> f64 {
-        let steps = self.difference_to(&Pitch {
-            note: 0,
-            octave: 2,
-            cents: 0.0,
-        });
-
-        0.5f64 * 0.15555f64 * 0.957458f64.powf(steps)
+        Scale::new_normal().get_radius(self)
     }
 }
 
@@ -135,21 +245,281 @@ impl Temperament {
         }
     }
 
+    pub fn new_default_werckmeister3() -> Temperament {
+        Temperament::new_freq_werckmeister3(440.0)
+    }
+
+    pub fn new_freq_werckmeister3(freq: f64) -> Temperament {
+        Temperament::WERCKMEISTER3 {
+            base_frequency: freq,
+            base_pitch: Pitch::from(9, 4),
+        }
+    }
+
+    pub fn new_default_kirnberger3() -> Temperament {
+        Temperament::new_freq_kirnberger3(440.0)
+    }
+
+    pub fn new_freq_kirnberger3(freq: f64) -> Temperament {
+        Temperament::KIRNBERGER3 {
+            base_frequency: freq,
+            base_pitch: Pitch::from(9, 4),
+        }
+    }
+
+    pub fn new_default_vallotti() -> Temperament {
+        Temperament::new_freq_vallotti(440.0)
+    }
+
+    pub fn new_freq_vallotti(freq: f64) -> Temperament {
+        Temperament::VALLOTTI {
+            base_frequency: freq,
+            base_pitch: Pitch::from(9, 4),
+        }
+    }
+
+    pub fn new_default_young() -> Temperament {
+        Temperament::new_freq_young(440.0)
+    }
+
+    pub fn new_freq_young(freq: f64) -> Temperament {
+        Temperament::YOUNG {
+            base_frequency: freq,
+            base_pitch: Pitch::from(9, 4),
+        }
+    }
+
+    pub fn new_default_meantone() -> Temperament {
+        Temperament::new_freq_meantone(440.0)
+    }
+
+    pub fn new_freq_meantone(freq: f64) -> Temperament {
+        Temperament::MEANTONE {
+            base_frequency: freq,
+            base_pitch: Pitch::from(9, 4),
+        }
+    }
+
+    pub fn new_default_just(tonic: Pitch) -> Temperament {
+        Temperament::new_freq_just(440.0, tonic, JUST_5_LIMIT)
+    }
+
+    pub fn new_freq_just(freq: f64, tonic: Pitch, ratios: [(u32, u32); 12]) -> Temperament {
+        Temperament::JUST {
+            base_frequency: freq,
+            base_pitch: Pitch::from(9, 4),
+            tonic,
+            ratios,
+        }
+    }
+
+    // None without any division
+    pub fn new_edo(divisions: u32, base_frequency: f64) -> Option<Temperament> {
+        Temperament::new_period_edo(divisions, 2.0, base_frequency)
+    }
+
+    // e.g. Bohlen-Pierce: 13 divisions of the period 3.0. None without any division or for
+    // a period that does not rise
+    pub fn new_period_edo(divisions: u32, period: f64, base_frequency: f64) -> Option<Temperament> {
+        if divisions == 0 || !period.is_finite() || period <= 1.0 {
+            return None;
+        }
+        Some(Temperament::EDO {
+            divisions,
+            period,
+            base_frequency,
+            base_octave: 4,
+        })
+    }
+
+    // Fractional steps keep their offset to the nearest key. Steps are semitones, also for
+    // EDO temperaments
     pub fn get_frequency(&self, steps: f64) -> f64 {
+        if let Temperament::EDO { base_frequency, .. } = *self {
+            return base_frequency * (2.0f64).powf(steps / 12.0);
+        }
+
+        if let Temperament::SCALA { scale, mapping } = self {
+            let key = steps.round();
+            let frequency = mapping.get_frequency(mapping.reference_key + key as i32, scale);
+            return frequency * (2.0f64).powf((steps - key) / 12.0);
+        }
+
+        let base_note = self.get_base_note().note;
+        let key = base_note + steps.round() as i32;
+        let deviation = self.get_deviation(key) - self.get_deviation(base_note);
+        self.get_base_frequency() * (2.0f64).powf((steps + deviation / 100.0) / 12.0)
+    }
+
+    // Nearest pitch of this temperament and the deviation of frequency from it in cents.
+    // For EDO temperaments the pitch of the nearest step, see step_for_frequency.
+    // None for frequencies that are not positive and finite
+    pub fn pitch_for_frequency(&self, frequency: f64) -> Option<(Pitch, f64)> {
+        if !frequency.is_finite() || frequency <= 0.0 {
+            return None;
+        }
+
+        if let Temperament::EDO { .. } = *self {
+            let (step, octave, cents) = self.step_for_frequency(frequency)?;
+            return Some((self.get_step_pitch(step, octave), cents));
+        }
+
+        let deviation = |steps: i32| {
+            let cents = 1200.0 * (frequency / self.get_frequency(f64::from(steps))).log2();
+            if cents.is_nan() {
+                None
+            } else {
+                Some(cents)
+            }
+        };
+
+        let estimate = (12.0 * (frequency / self.get_base_frequency()).log2()).round() as i32;
+
+        // Unmapped keys of Scala temperaments have no frequency
+        let (mut nearest, mut cents) = (0..128)
+            .flat_map(|distance| vec![estimate - distance, estimate + distance])
+            .filter_map(|steps| deviation(steps).map(|cents| (steps, cents)))
+            .next()
+            .unwrap_or((estimate, f64::NAN));
+
+        // Deviations shrink monotonically towards the nearest pitch
+        let direction = if cents > 0.0 { 1 } else { -1 };
+        let mut steps = nearest;
+        for _ in 0..1024 {
+            steps += direction;
+            if let Some(candidate) = deviation(steps) {
+                if candidate.abs() >= cents.abs() {
+                    break;
+                }
+                nearest = steps;
+                cents = candidate;
+            }
+        }
+
+        Some((self.get_base_note().add(f64::from(nearest) * 100.0), cents))
+    }
+
+    // Frequency of a step within an octave, or within the period for EDO temperaments.
+    // EDO octaves count periods from the base octave
+    pub fn get_step_frequency(&self, step: i32, octave: i32) -> f64 {
+        match *self {
+            Temperament::EDO {
+                divisions,
+                period,
+                base_frequency,
+                base_octave,
+            } => {
+                let steps = (octave - base_octave) * divisions as i32 + step;
+                base_frequency * period.powf(f64::from(steps) / f64::from(divisions))
+            }
+            _ => Pitch::from(step, octave).to_frequency(self),
+        }
+    }
+
+    // Nearest 12-tone pitch of a step and its offset in cents, for use with scales and pipes
+    pub fn get_step_pitch(&self, step: i32, octave: i32) -> Pitch {
+        match *self {
+            Temperament::EDO { .. } => {
+                let base_note = self.get_base_note();
+                let cents = 1200.0
+                    * (self.get_step_frequency(step, octave) / self.get_base_frequency()).log2();
+                let semitones = (cents / 100.0).round();
+                Pitch::from_with_cents(
+                    base_note.note + semitones as i32,
+                    base_note.octave,
+                    cents - semitones * 100.0,
+                )
+            }
+            _ => Pitch::from(step, octave),
+        }
+    }
+
+    // Nearest step as (step, octave, cents deviation), see get_step_frequency.
+    // None for frequencies that are not positive and finite
+    pub fn step_for_frequency(&self, frequency: f64) -> Option<(i32, i32, f64)> {
+        if !frequency.is_finite() || frequency <= 0.0 {
+            return None;
+        }
+
+        match *self {
+            Temperament::EDO {
+                divisions,
+                period,
+                base_frequency,
+                base_octave,
+            } => {
+                let divisions = divisions as i32;
+                let steps = (f64::from(divisions) * (frequency / base_frequency).ln() / period.ln())
+                    .round() as i32;
+                let (step, octave) = (
+                    steps.rem_euclid(divisions),
+                    base_octave + steps.div_euclid(divisions),
+                );
+                let cents = 1200.0 * (frequency / self.get_step_frequency(step, octave)).log2();
+                Some((step, octave, cents))
+            }
+            _ => {
+                let (pitch, cents) = self.pitch_for_frequency(frequency)?;
+                Some((pitch.note, pitch.octave, cents))
+            }
+        }
+    }
+
+    pub fn get_steps_per_octave(&self) -> i32 {
+        match *self {
+            Temperament::EDO { divisions, .. } => divisions as i32,
+            _ => 12,
+        }
+    }
+
+    // Deviation of a note from equal temperament in cents
+    fn get_deviation(&self, note: i32) -> f64 {
+        let note = note.rem_euclid(12);
+        let cents = match self {
+            Temperament::EQUAL { .. } => return 0.0,
+            Temperament::WERCKMEISTER3 { .. } => WERCKMEISTER3_CENTS[note as usize],
+            Temperament::KIRNBERGER3 { .. } => KIRNBERGER3_CENTS[note as usize],
+            Temperament::VALLOTTI { .. } => VALLOTTI_CENTS[note as usize],
+            Temperament::YOUNG { .. } => YOUNG_CENTS[note as usize],
+            Temperament::MEANTONE { .. } => MEANTONE_CENTS[note as usize],
+            Temperament::JUST { tonic, ratios, .. } => {
+                let interval = (note - tonic.note).rem_euclid(12);
+                let (numerator, denominator) = ratios[interval as usize];
+                let ratio = f64::from(numerator) / f64::from(denominator);
+                1200.0 * ratio.log2() - f64::from(interval) * 100.0 + f64::from(note) * 100.0
+            }
+            Temperament::EDO { .. } | Temperament::SCALA { .. } => return 0.0,
+        };
+        cents - f64::from(note) * 100.0
+    }
+
+    pub fn get_base_frequency(&self) -> f64 {
         match self {
-            &Temperament::EQUAL {
-                base_frequency: base,
-                ..
-            } => base * (2.0f64).powf(steps / 12.0),
+            Temperament::EQUAL { base_frequency, .. }
+            | Temperament::WERCKMEISTER3 { base_frequency, .. }
+            | Temperament::KIRNBERGER3 { base_frequency, .. }
+            | Temperament::VALLOTTI { base_frequency, .. }
+            | Temperament::YOUNG { base_frequency, .. }
+            | Temperament::MEANTONE { base_frequency, .. }
+            | Temperament::JUST { base_frequency, .. }
+            | Temperament::EDO { base_frequency, .. } => *base_frequency,
+            Temperament::SCALA { mapping, .. } => mapping.reference_frequency,
         }
     }
 
     pub fn get_base_note(&self) -> Pitch {
         match self {
-            &Temperament::EQUAL {
-                base_pitch: ref pitch,
-                ..
-            } => pitch.clone(),
+            Temperament::EQUAL { base_pitch, .. }
+            | Temperament::WERCKMEISTER3 { base_pitch, .. }
+            | Temperament::KIRNBERGER3 { base_pitch, .. }
+            | Temperament::VALLOTTI { base_pitch, .. }
+            | Temperament::YOUNG { base_pitch, .. }
+            | Temperament::MEANTONE { base_pitch, .. }
+            | Temperament::JUST { base_pitch, .. } => base_pitch.clone(),
+            Temperament::EDO { base_octave, .. } => Pitch::from(0, *base_octave),
+            Temperament::SCALA { mapping, .. } => {
+                Pitch::from_midi(mapping.reference_key, SCIENTIFIC_MIDDLE_C)
+            }
         }
     }
 }
@@ -228,4 +598,181 @@ mod tests {
         assert!(approx(temp.get_frequency(-1.0), 415.3046975799451, 0.0001));
         assert!(approx(temp.get_frequency(3.0), 523.2511306011972, 0.0001));
     }
+
+    #[test]
+    fn test_well_temperaments() {
+        let temp = Temperament::new_default_werckmeister3();
+        assert!(approx(temp.get_frequency(0.0), 440.0, 0.0001));
+        assert!(approx(temp.get_frequency(12.0), 880.0, 0.0001));
+        // C5 lies 888.270 - 1200 cents from A4
+        assert!(approx(
+            Pitch::from(0, 5).to_frequency(&temp),
+            440.0 * (2.0f64).powf(311.73 / 1200.0),
+            0.0001,
+        ));
+
+        // Pure major third C-E in meantone
+        let temp = Temperament::new_default_meantone();
+        let c = Pitch::from(0, 4).to_frequency(&temp);
+        let e = Pitch::from(4, 4).to_frequency(&temp);
+        assert!(approx(e / c, 1.25, 0.0001));
+
+        // Pure fifth F#-C# in Vallotti, tempered fifth C-G
+        let temp = Temperament::new_default_vallotti();
+        let f_sharp = Pitch::from(6, 4).to_frequency(&temp);
+        let c_sharp = Pitch::from(1, 5).to_frequency(&temp);
+        assert!(approx(c_sharp / f_sharp, 1.5, 0.0001));
+        let c = Pitch::from(0, 4).to_frequency(&temp);
+        let g = Pitch::from(7, 4).to_frequency(&temp);
+        assert!(g / c < 1.5);
+    }
+
+    #[test]
+    fn test_edo_temperament() {
+        let temp = Temperament::new_edo(31, 261.6256).unwrap();
+        assert_eq!(temp.get_steps_per_octave(), 31);
+        assert!(approx(temp.get_step_frequency(0, 4), 261.6256, 0.0001));
+        assert!(approx(temp.get_step_frequency(0, 5), 523.2512, 0.0001));
+        assert!(approx(temp.get_step_frequency(0, 3), 130.8128, 0.0001));
+        // Near-pure major third
+        assert!(approx(
+            temp.get_step_frequency(10, 4) / 261.6256,
+            1.25,
+            0.002,
+        ));
+        assert!(approx(temp.get_step_frequency(-31, 4), 130.8128, 0.0001));
+
+        // Pitches keep their meaning, steps map to the nearest pitch with an offset
+        assert!(approx(
+            Pitch::from(0, 5).to_frequency(&temp),
+            523.2512,
+            0.0001,
+        ));
+        assert!(approx(Pitch::from(9, 4).to_frequency(&temp), 440.0, 0.001));
+        let pitch = temp.get_step_pitch(1, 4);
+        assert_eq!(pitch.get_note(), 0);
+        assert!(approx(pitch.get_cents(), 1200.0 / 31.0, 0.0001));
+        assert_eq!(temp.get_step_pitch(0, 5), Pitch::from(0, 5));
+        assert!(approx(
+            temp.get_step_pitch(18, 4).to_frequency(&temp),
+            temp.get_step_frequency(18, 4),
+            0.0001,
+        ));
+        assert!(approx(
+            temp.get_step_pitch(0, 3).get_tnm_radius(),
+            Pitch::from(0, 3).get_tnm_radius(),
+            0.0000001,
+        ));
+
+        // Bohlen-Pierce, 13 steps per tritave
+        let temp = Temperament::new_period_edo(13, 3.0, 100.0).unwrap();
+        assert!(approx(temp.get_step_frequency(0, 5), 300.0, 0.0001));
+        assert!(approx(temp.get_step_frequency(13, 4), 300.0, 0.0001));
+        assert!(approx(
+            temp.get_step_pitch(1, 4).to_frequency(&temp),
+            100.0 * (3.0f64).powf(1.0 / 13.0),
+            0.0001,
+        ));
+
+        assert!(Temperament::new_edo(0, 261.6256).is_none());
+        assert!(Temperament::new_period_edo(12, 1.0, 261.6256).is_none());
+        assert!(Temperament::new_period_edo(12, f64::NAN, 261.6256).is_none());
+
+        let temp = Temperament::new_default_equal();
+        assert!(approx(temp.get_step_frequency(9, 4), 440.0, 0.0001));
+    }
+
+    #[test]
+    fn test_just_temperament() {
+        let temp = Temperament::new_default_just(Pitch::from(2, 4));
+        assert!(approx(Pitch::from(9, 4).to_frequency(&temp), 440.0, 0.0001));
+
+        let d = Pitch::from(2, 4).to_frequency(&temp);
+        assert!(approx(d, 440.0 / 1.5, 0.0001));
+        assert!(approx(
+            Pitch::from(6, 4).to_frequency(&temp) / d,
+            1.25,
+            0.0001,
+        ));
+        assert!(approx(
+            Pitch::from(11, 4).to_frequency(&temp) / d,
+            5.0 / 3.0,
+            0.0001,
+        ));
+        assert!(approx(
+            Pitch::from(1, 5).to_frequency(&temp) / d,
+            15.0 / 8.0,
+            0.0001,
+        ));
+        assert!(approx(
+            Pitch::from(2, 2).to_frequency(&temp),
+            d / 4.0,
+            0.0001,
+        ));
+
+        let temp = Temperament::new_freq_just(440.0, Pitch::from(0, 0), JUST_7_LIMIT);
+        let c = Pitch::from(0, 4).to_frequency(&temp);
+        assert!(approx(
+            Pitch::from(10, 4).to_frequency(&temp) / c,
+            1.75,
+            0.0001,
+        ));
+    }
+
+    #[test]
+    fn test_pitch_for_frequency() {
+        let temp = Temperament::new_default_equal();
+        let (pitch, cents) = temp.pitch_for_frequency(440.0).unwrap();
+        assert_eq!(pitch, Pitch::from(9, 4));
+        assert!(approx(cents, 0.0, 0.0001));
+
+        let (pitch, cents) = temp.pitch_for_frequency(445.0).unwrap();
+        assert_eq!(pitch, Pitch::from(9, 4));
+        assert!(approx(cents, 19.56, 0.01));
+
+        let (pitch, cents) = temp.pitch_for_frequency(32.0).unwrap();
+        assert_eq!(pitch, Pitch::from(0, 1));
+        assert!(approx(cents, -37.63, 0.01));
+
+        let temp = Temperament::new_default_meantone();
+        let g_sharp = Pitch::from(8, 4).to_frequency(&temp);
+        let (pitch, cents) = temp.pitch_for_frequency(g_sharp * 0.999).unwrap();
+        assert_eq!(pitch, Pitch::from(8, 4));
+        assert!(approx(cents, -1.73, 0.01));
+
+        let temp = Temperament::new_edo(31, 261.6256).unwrap();
+        let (step, octave, cents) = temp.step_for_frequency(523.2512).unwrap();
+        assert_eq!((step, octave), (0, 5));
+        assert!(approx(cents, 0.0, 0.0001));
+        let (step, octave, cents) = temp
+            .step_for_frequency(temp.get_step_frequency(18, 5) * 1.001)
+            .unwrap();
+        assert_eq!((step, octave), (18, 5));
+        assert!(approx(cents, 1.73, 0.01));
+
+        // The 31-EDO fifth lies 3.23 cents below the equal tempered G
+        let (pitch, cents) = temp
+            .pitch_for_frequency(temp.get_step_frequency(18, 5))
+            .unwrap();
+        assert_eq!(pitch, temp.get_step_pitch(18, 5));
+        assert_eq!(pitch.get_note(), 7);
+        assert!(approx(pitch.get_cents(), -3.23, 0.01));
+        assert!(approx(cents, 0.0, 0.0001));
+        assert_eq!(
+            temp.pitch_for_frequency(523.2512).unwrap().0,
+            Pitch::from(0, 5)
+        );
+
+        let temp = Temperament::new_default_equal();
+        assert_eq!(temp.step_for_frequency(440.0).unwrap(), (9, 4, 0.0));
+
+        // A tuner without a signal
+        assert_eq!(temp.pitch_for_frequency(0.0), None);
+        assert_eq!(temp.pitch_for_frequency(-440.0), None);
+        assert_eq!(temp.pitch_for_frequency(f64::INFINITY), None);
+        assert_eq!(temp.pitch_for_frequency(f64::NAN), None);
+        let temp = Temperament::new_edo(31, 261.6256).unwrap();
+        assert_eq!(temp.step_for_frequency(0.0), None);
+        assert_eq!(temp.pitch_for_frequency(0.0), None);
+    }
 }
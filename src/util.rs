@@ -26,10 +26,129 @@ pub fn approx(to_test: f64, expect: f64, margin: f64) -> bool {
     diff < margin && diff > -margin
 }
 
+// 2' C principal shared by the flue pipe tests
+#[cfg(test)]
+pub fn c4_pipe() -> ::pipe::Pipe {
+    let mut pipe = ::pipe::Pipe::new();
+    pipe.set_frequency(261.63)
+        .set_radius(0.02)
+        .set_mouth_ratio(0.25)
+        .set_cutup_ratio(0.25)
+        .set_air_density(1.2)
+        .set_wind_pressure(mmH2O_to_pa(60.0));
+    pipe
+}
+
 pub fn speed_of_sound(air_temperature: f64) -> f64 {
     331.6 + 0.6 * air_temperature
 }
 
+// Cramer (1993), air_temperature in C, relative_humidity from 0 to 1, air_pressure in Pa,
+// co2_fraction as mole fraction. Valid from 0 to 30 C and 75 to 102 kPa
+pub fn speed_of_sound_cramer(
+    air_temperature: f64,
+    relative_humidity: f64,
+    air_pressure: f64,
+    co2_fraction: f64,
+) -> f64 {
+    let t = air_temperature;
+    let kelvin = t + 273.15;
+    let p = air_pressure;
+
+    // Mole fraction of water vapour, saturation vapour pressure after Davis (1992)
+    let enhancement = 1.00062 + 3.14e-8 * p + 5.6e-7 * t.powi(2);
+    let saturation_pressure = (1.2811805e-5 * kelvin.powi(2) - 1.9509874e-2 * kelvin + 34.04926034
+        - 6.3536311e3 / kelvin)
+        .exp();
+    let xw = relative_humidity * enhancement * saturation_pressure / p;
+    let xc = co2_fraction;
+
+    331.5024 + 0.603055 * t - 0.000528 * t.powi(2)
+        + (51.471935 + 0.1495874 * t - 0.000782 * t.powi(2)) * xw
+        + (-1.82e-7 + 3.73e-8 * t - 2.93e-10 * t.powi(2)) * p
+        + (-85.20931 - 0.228525 * t + 5.91e-5 * t.powi(2)) * xc
+        - 2.835149 * xw.powi(2)
+        - 2.15e-13 * p.powi(2)
+        + 29.179762 * xc.powi(2)
+        + 0.000486 * xw * p * xc
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SoundSpeedModel {
+    // speed_of_sound, dry air
+    Linear,
+
+    // speed_of_sound_cramer
+    Cramer {
+        relative_humidity: f64,
+        air_pressure: f64,
+        co2_fraction: f64,
+    },
+}
+
+impl SoundSpeedModel {
+    // Sea level pressure and 400 ppm CO2
+    pub fn new_cramer(relative_humidity: f64) -> Self {
+        SoundSpeedModel::Cramer {
+            relative_humidity,
+            air_pressure: 101_325.0,
+            co2_fraction: 0.0004,
+        }
+    }
+
+    pub fn get_speed_of_sound(&self, air_temperature: f64) -> f64 {
+        match *self {
+            SoundSpeedModel::Linear => speed_of_sound(air_temperature),
+            SoundSpeedModel::Cramer {
+                relative_humidity,
+                air_pressure,
+                co2_fraction,
+            } => speed_of_sound_cramer(
+                air_temperature,
+                relative_humidity,
+                air_pressure,
+                co2_fraction,
+            ),
+        }
+    }
+}
+
 pub fn air_speed(wind_pressure: f64, air_density: f64) -> f64 {
     (2.0 * (wind_pressure / air_density)).sqrt()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_speed_of_sound_cramer() {
+        // Dry air at 0 C with 314 ppm CO2, the reference value of Cramer
+        assert!(approx(
+            speed_of_sound_cramer(0.0, 0.0, 101_325.0, 0.000314),
+            331.46,
+            0.01,
+        ));
+        assert!(approx(
+            speed_of_sound_cramer(20.0, 0.0, 101_325.0, 0.000314),
+            343.37,
+            0.01,
+        ));
+
+        // Humid air is lighter, exhaled CO2 heavier
+        let model = SoundSpeedModel::new_cramer(0.0);
+        let humid = SoundSpeedModel::new_cramer(0.8);
+        assert!(humid.get_speed_of_sound(20.0) > model.get_speed_of_sound(20.0) + 0.5);
+        let crowded = SoundSpeedModel::Cramer {
+            relative_humidity: 0.0,
+            air_pressure: 101_325.0,
+            co2_fraction: 0.003,
+        };
+        assert!(crowded.get_speed_of_sound(20.0) < model.get_speed_of_sound(20.0) - 0.2);
+
+        assert_eq!(
+            SoundSpeedModel::Linear.get_speed_of_sound(20.0),
+            speed_of_sound(20.0)
+        );
+    }
+}